@@ -0,0 +1,95 @@
+use std::{fmt::Debug, hash::Hash};
+
+use crate::graph_util::{get_heights, CallGraph};
+
+/// A function whose direct callees are at very different levels, mixing high level
+/// orchestration with low level details
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MixedAbstraction<T> {
+    pub function: T,
+    /// Difference between the levels of the highest and lowest callees
+    pub spread: usize,
+    /// Callee with the deepest call chain below it, and its level
+    pub highest: (T, usize),
+    /// Callee with the shallowest call chain below it, and its level
+    pub lowest: (T, usize),
+}
+
+/// Find functions whose direct callees' levels differ by at least `threshold` (and at least 1),
+/// largest spread first. The level of a function is the length of the longest call chain
+/// below it, so low level helpers are at level 0.
+///
+/// Levels are heights rather than depths from the roots, since a callee's depth depends on
+/// its shallowest caller anywhere in the project, not on what the callee itself does.
+pub fn find_mixed_abstractions<T>(
+    graph: &CallGraph<T>,
+    threshold: usize,
+) -> Vec<MixedAbstraction<T>>
+where
+    T: Copy + Hash + Eq + Debug,
+{
+    let levels = get_heights(graph);
+
+    // callees of each function, in order of first appearance
+    let callees = graph.nodes().map(|node| {
+        let function_callees = graph
+            .callees(node)
+            .into_iter()
+            // calls to itself aren't a lower level
+            .filter(|&callee| callee != node)
+            .map(|callee| *graph.item(callee))
+            .collect::<Vec<_>>();
+
+        (*graph.item(node), function_callees)
+    });
+
+    let mut offenders = callees
+        .into_iter()
+        .filter_map(|(function, function_callees)| {
+            let callee_levels = function_callees
+                .into_iter()
+                .map(|callee| (callee, levels[&callee]))
+                .collect::<Vec<_>>();
+
+            let &highest = callee_levels.iter().max_by_key(|(_, level)| *level)?;
+            let &lowest = callee_levels.iter().min_by_key(|(_, level)| *level)?;
+            let spread = highest.1 - lowest.1;
+
+            (spread >= threshold.max(1)).then_some(MixedAbstraction {
+                function,
+                spread,
+                highest,
+                lowest,
+            })
+        })
+        .collect::<Vec<_>>();
+
+    offenders.sort_by_key(|offender| std::cmp::Reverse(offender.spread));
+
+    offenders
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph_util::CallGraph;
+
+    use super::{find_mixed_abstractions, MixedAbstraction};
+
+    #[test]
+    fn test_find_mixed_abstractions() {
+        // 0 orchestrates 1 (which goes 3 levels down) but also calls the leaf 5 directly
+        let calls = CallGraph::new(&[(0, 1), (1, 2), (2, 3), (3, 4), (0, 5), (1, 6), (6, 4)]);
+
+        assert_eq!(
+            find_mixed_abstractions(&calls, 2),
+            vec![MixedAbstraction {
+                function: 0,
+                spread: 3,
+                highest: (1, 3),
+                lowest: (5, 0),
+            }]
+        );
+        assert_eq!(find_mixed_abstractions(&calls, 1).len(), 2);
+        assert_eq!(find_mixed_abstractions(&calls, 4), vec![]);
+    }
+}
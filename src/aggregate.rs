@@ -0,0 +1,148 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use lsp_types::{CallHierarchyItem, Url};
+
+use crate::{
+    external,
+    symbol_arena::{SymbolArena, SymbolId},
+};
+
+/// Files marking the root directory of a crate / package
+const MANIFEST_FILES: [&str; 5] = [
+    "Cargo.toml",
+    "pom.xml",
+    "build.gradle",
+    "build.gradle.kts",
+    "package.json",
+];
+
+/// Level to collapse function calls to
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Granularity {
+    File,
+    /// Directory containing the file
+    Module,
+    /// Crate / package containing the file, found by its manifest file
+    Package,
+}
+
+/// Get the name of the file / module / package containing an item. Items outside the
+/// project are grouped by their external package.
+pub fn get_group_name(item: &CallHierarchyItem, root: &Url, granularity: Granularity) -> String {
+    let Some(relative_path) = item.uri.as_str().strip_prefix(root.as_str()) else {
+        return format!("[{}]", external::get_external_package_name(&item.uri));
+    };
+
+    let relative_path = relative_path.trim_start_matches('/');
+
+    let group = match granularity {
+        Granularity::File => Some(relative_path.to_string()),
+        Granularity::Module => relative_path
+            .rsplit_once('/')
+            .map(|(module, _)| module.to_string()),
+        Granularity::Package => get_package_dir(item, root),
+    };
+
+    group
+        .filter(|group| !group.is_empty())
+        .unwrap_or_else(|| ".".to_string())
+}
+
+/// Get the directory of the closest manifest above the item's file, relative to the root
+fn get_package_dir(item: &CallHierarchyItem, root: &Url) -> Option<String> {
+    let path = item.uri.to_file_path().ok()?;
+    let root = root.to_file_path().ok()?;
+
+    let package_dir = path
+        .ancestors()
+        .take_while(|dir| dir.starts_with(&root))
+        .find(|dir| has_manifest(dir))?;
+
+    Some(
+        package_dir
+            .strip_prefix(&root)
+            .ok()?
+            .to_string_lossy()
+            .to_string(),
+    )
+}
+
+fn has_manifest(dir: &Path) -> bool {
+    MANIFEST_FILES.iter().any(|file| dir.join(file).is_file())
+}
+
+/// Map each function call to a call between the groups of its caller and callee
+pub fn group_calls(
+    arena: &SymbolArena,
+    root: &Url,
+    calls: &[(SymbolId, SymbolId)],
+    granularity: Granularity,
+) -> Vec<(String, String)> {
+    let groups = arena
+        .ids()
+        .map(|id| (id, get_group_name(arena.item(id), root, granularity)))
+        .collect::<HashMap<_, _>>();
+
+    calls
+        .iter()
+        .map(|(s, t)| (groups[s].clone(), groups[t].clone()))
+        .collect()
+}
+
+/// Collapse function calls into calls between groups, in order of first appearance.
+/// Calls inside a group are dropped, and calls between the same groups are merged.
+pub fn aggregate_calls(
+    arena: &SymbolArena,
+    root: &Url,
+    calls: &[(SymbolId, SymbolId)],
+    granularity: Granularity,
+) -> Vec<(String, String)> {
+    let mut seen = HashSet::new();
+
+    group_calls(arena, root, calls, granularity)
+        .into_iter()
+        .filter(|(s, t)| s != t && seen.insert((s.clone(), t.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{SymbolKind, Url};
+
+    use super::{get_group_name, Granularity};
+    use crate::test_util::item;
+
+    #[test]
+    fn test_get_group_name() {
+        let root = Url::parse("file:///project/").unwrap();
+        let nested = item(
+            "f",
+            SymbolKind::FUNCTION,
+            "file:///project/src/api/users.rs",
+        );
+        let top = item("f", SymbolKind::FUNCTION, "file:///project/build.rs");
+
+        assert_eq!(
+            get_group_name(&nested, &root, Granularity::File),
+            "src/api/users.rs"
+        );
+        assert_eq!(
+            get_group_name(&nested, &root, Granularity::Module),
+            "src/api"
+        );
+        assert_eq!(get_group_name(&top, &root, Granularity::Module), ".");
+
+        let external = item(
+            "f",
+            SymbolKind::FUNCTION,
+            "file:///home/user/.cargo/registry/src/index/serde-1.0.0/src/lib.rs",
+        );
+        assert_eq!(
+            get_group_name(&external, &root, Granularity::Module),
+            "[serde]"
+        );
+    }
+}
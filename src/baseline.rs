@@ -0,0 +1,273 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    error::Error,
+    fs,
+    path::Path,
+};
+
+use lsp_types::{CallHierarchyItem, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    build_call_hierarchy_item_name,
+    dead_code::get_enclosing_block,
+    symbol_arena::{SymbolArena, SymbolId},
+    Depths, MaxDepths,
+};
+
+/// Bump when the baseline format changes
+const BASELINE_VERSION: u32 = 2;
+
+/// Known problems, so that only new ones (or ones that got worse) fail a run
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    version: u32,
+    /// Problems keyed by [`get_symbol_key`]
+    problems: BTreeMap<String, ProblemDepths>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProblemDepths {
+    pub min_depth: usize,
+    pub max_depth: usize,
+}
+
+impl ProblemDepths {
+    fn spread(&self) -> usize {
+        self.max_depth - self.min_depth
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BaselineComparison {
+    pub new_problems: Vec<String>,
+    /// Problems whose depth spread grew
+    pub worse_problems: Vec<String>,
+    pub fixed_problems: Vec<String>,
+}
+
+impl BaselineComparison {
+    pub fn has_regressions(&self) -> bool {
+        !self.new_problems.is_empty() || !self.worse_problems.is_empty()
+    }
+}
+
+impl Baseline {
+    /// Collect the depths of the given problems. The max depth of each problem also accounts
+    /// for its longest path, if `max_depths` were computed.
+    pub fn new(
+        arena: &SymbolArena,
+        root: &Url,
+        depths: &Depths<SymbolId>,
+        max_depths: Option<&MaxDepths<SymbolId>>,
+        problems: &HashSet<SymbolId>,
+    ) -> Self {
+        let longest = max_depths
+            .into_iter()
+            .flatten()
+            .map(|(item, path)| (*item, path.depth))
+            .collect::<HashMap<_, _>>();
+
+        let mut sources: HashMap<Url, Option<String>> = HashMap::new();
+        let mut baseline_problems: BTreeMap<String, ProblemDepths> = BTreeMap::new();
+
+        for (item, paths) in depths.iter().filter(|(item, _)| problems.contains(item)) {
+            let depths = paths
+                .iter()
+                .map(|path| path.depth)
+                .chain(longest.get(item).copied());
+            let (Some(min_depth), Some(max_depth)) = (depths.clone().min(), depths.max()) else {
+                continue;
+            };
+
+            let symbol = arena.item(*item);
+            let source = sources
+                .entry(symbol.uri.clone())
+                .or_insert_with(|| fs::read_to_string(symbol.uri.to_file_path().ok()?).ok());
+
+            // symbols still sharing a key are merged, keeping their widest depths
+            baseline_problems
+                .entry(get_symbol_key(symbol, root, source.as_deref()))
+                .and_modify(|depths| {
+                    depths.min_depth = depths.min_depth.min(min_depth);
+                    depths.max_depth = depths.max_depth.max(max_depth);
+                })
+                .or_insert(ProblemDepths {
+                    min_depth,
+                    max_depth,
+                });
+        }
+
+        Self {
+            version: BASELINE_VERSION,
+            problems: baseline_problems,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let baseline: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        if baseline.version != BASELINE_VERSION {
+            return Err(format!(
+                "unsupported baseline version {} (expected {})",
+                baseline.version, BASELINE_VERSION
+            )
+            .into());
+        }
+
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Compare the problems of the current run to the baseline
+    pub fn compare(&self, current: &Baseline) -> BaselineComparison {
+        let mut comparison = BaselineComparison::default();
+
+        for (key, depths) in &current.problems {
+            match self.problems.get(key) {
+                None => comparison.new_problems.push(key.clone()),
+                Some(known) if depths.spread() > known.spread() => {
+                    comparison.worse_problems.push(key.clone())
+                }
+                Some(_) => {}
+            }
+        }
+
+        comparison.fixed_problems = self
+            .problems
+            .keys()
+            .filter(|key| !current.problems.contains_key(*key))
+            .cloned()
+            .collect();
+
+        comparison
+    }
+}
+
+/// Identify a symbol by its file, the header of the block containing it (e.g.
+/// `impl Display for Foo`, so that methods of the same name in one file are told apart) and its
+/// full name (including parameters for servers that add them, e.g. jdtls), but not by its
+/// position, so keys survive unrelated edits
+pub fn get_symbol_key(item: &CallHierarchyItem, root: &Url, source: Option<&str>) -> String {
+    let Some(relative_path) = item.uri.as_str().strip_prefix(root.as_str()) else {
+        return build_call_hierarchy_item_name(item, root);
+    };
+
+    let container = source
+        .and_then(|source| get_enclosing_block(source, item))
+        .map(|block| block.trim_end_matches('{').trim_end());
+
+    match container {
+        Some(container) => format!("{}:{}:{}", relative_path, container, item.name),
+        None => format!("{}:{}", relative_path, item.name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use lsp_types::{SymbolKind, Url};
+
+    use super::{get_symbol_key, Baseline, BaselineComparison, ProblemDepths, BASELINE_VERSION};
+    use crate::{symbol_arena::SymbolArena, test_util::item_at, CallPath};
+
+    const A: &str = "file:///project/src/a.rs";
+
+    fn baseline(problems: &[(&str, usize, usize)]) -> Baseline {
+        Baseline {
+            version: BASELINE_VERSION,
+            problems: problems
+                .iter()
+                .map(|&(key, min_depth, max_depth)| {
+                    (
+                        key.to_string(),
+                        ProblemDepths {
+                            min_depth,
+                            max_depth,
+                        },
+                    )
+                })
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_compare() {
+        let known = baseline(&[("a.rs:f", 1, 3), ("a.rs:g", 1, 2), ("b.rs:h", 2, 4)]);
+        let current = baseline(&[("a.rs:f", 2, 3), ("a.rs:g", 1, 4), ("c.rs:k", 1, 2)]);
+
+        let comparison = known.compare(&current);
+
+        assert_eq!(
+            comparison,
+            BaselineComparison {
+                new_problems: vec!["c.rs:k".to_string()],
+                worse_problems: vec!["a.rs:g".to_string()],
+                fixed_problems: vec!["b.rs:h".to_string()],
+            }
+        );
+        assert!(comparison.has_regressions());
+        assert!(!known.compare(&known).has_regressions());
+    }
+
+    #[test]
+    fn test_get_symbol_key() {
+        let root = Url::parse("file:///project/").unwrap();
+        let source = "fn main() {}\n\nimpl Display for A {\n    fn fmt() {}\n}\n\nimpl Display for B {\n    fn fmt() {}\n}\n";
+
+        assert_eq!(
+            get_symbol_key(
+                &item_at("main", SymbolKind::METHOD, A, 0, 3),
+                &root,
+                Some(source)
+            ),
+            "src/a.rs:main"
+        );
+        assert_eq!(
+            get_symbol_key(
+                &item_at("fmt", SymbolKind::METHOD, A, 3, 7),
+                &root,
+                Some(source)
+            ),
+            "src/a.rs:impl Display for A:fmt"
+        );
+        assert_eq!(
+            get_symbol_key(
+                &item_at("fmt", SymbolKind::METHOD, A, 7, 7),
+                &root,
+                Some(source)
+            ),
+            "src/a.rs:impl Display for B:fmt"
+        );
+    }
+
+    #[test]
+    fn test_new_with_max_depths() {
+        let root = Url::parse("file:///project/").unwrap();
+        let mut arena = SymbolArena::default();
+        let f = arena.intern(item_at("f", SymbolKind::METHOD, A, 0, 3));
+
+        let path = |depth| CallPath {
+            depth,
+            path: vec![f; depth + 1],
+        };
+        let depths = vec![(f, vec![path(1), path(2)])];
+        let max_depths = vec![(f, path(4))];
+        let problems = [f].into_iter().collect();
+
+        assert_eq!(
+            Baseline::new(&arena, &root, &depths, None, &problems),
+            baseline(&[("src/a.rs:f", 1, 2)])
+        );
+        assert_eq!(
+            Baseline::new(&arena, &root, &depths, Some(&max_depths), &problems),
+            baseline(&[("src/a.rs:f", 1, 4)])
+        );
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use log::{debug, warn};
+use lsp_types::{CallHierarchyItem, DocumentSymbol, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::{update_exact_definitions, CallGraphOptions};
+
+const CACHE_DIR: &str = ".code_depth";
+const CACHE_FILE: &str = "cache.json";
+
+/// Bump when the cache format or the file hash changes, so old caches are discarded
+const CACHE_VERSION: &str = "3-fnv1a64";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// Per file symbols and call edges from previous runs.
+///
+/// Each file is keyed by the hash of its content. A call edge stays valid as long as
+/// both the caller's and the callee's files are unchanged, everything else is re-queried.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnalysisCache {
+    version: String,
+    options: Option<CallGraphOptions>,
+    files: HashMap<Url, CachedFile>,
+    calls: Vec<(CallHierarchyItem, CallHierarchyItem)>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedFile {
+    hash: u64,
+    /// All document symbols of the file, including containers like classes and traits
+    symbols: Vec<DocumentSymbol>,
+}
+
+impl Default for AnalysisCache {
+    fn default() -> Self {
+        Self {
+            version: CACHE_VERSION.to_string(),
+            options: None,
+            files: HashMap::new(),
+            calls: vec![],
+        }
+    }
+}
+
+impl AnalysisCache {
+    /// Load the cache of the project at `project_path`, or an empty cache if there is none
+    pub fn load(project_path: &Path) -> Self {
+        let cache_file = cache_dir(project_path).join(CACHE_FILE);
+
+        let Ok(content) = fs::read(&cache_file) else {
+            return Self::default();
+        };
+
+        // check the version first, older caches may have a different format
+        let version = serde_json::from_slice::<serde_json::Value>(&content)
+            .ok()
+            .and_then(|cache| cache.get("version").cloned());
+
+        if version.as_ref().and_then(|v| v.as_str()) != Some(CACHE_VERSION) {
+            debug!("discarding cache with old version: {:?}", cache_file);
+            return Self::default();
+        }
+
+        match serde_json::from_slice::<Self>(&content) {
+            Ok(cache) => cache,
+            Err(e) => {
+                warn!("failed to parse cache {:?}: {}", cache_file, e);
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self, project_path: &Path) -> Result<(), Box<dyn Error>> {
+        let cache_dir = cache_dir(project_path);
+        fs::create_dir_all(&cache_dir)?;
+
+        fs::write(cache_dir.join(CACHE_FILE), serde_json::to_vec(self)?)?;
+
+        Ok(())
+    }
+
+    /// Remove the cache of the project at `project_path`
+    pub fn clear(project_path: &Path) -> std::io::Result<()> {
+        let cache_dir = cache_dir(project_path);
+
+        if cache_dir.exists() {
+            fs::remove_dir_all(cache_dir)?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop everything that depends on files that changed since the last run.
+    ///
+    /// Returns the files that need to be re-queried, including files that no longer exist.
+    pub(crate) fn invalidate(
+        &mut self,
+        workspace_files: &HashSet<Url>,
+        options: &CallGraphOptions,
+    ) -> HashSet<Url> {
+        // different options produce a different graph
+        if self.options.as_ref() != Some(options) {
+            self.options = Some(options.clone());
+            self.files.clear();
+            self.calls.clear();
+        }
+
+        let mut changed_files = self
+            .files
+            .keys()
+            .filter(|file| !workspace_files.contains(file))
+            .cloned()
+            .collect::<HashSet<_>>();
+
+        for file in workspace_files {
+            let hash = hash_file(file);
+            let cached_hash = self.files.get(file).map(|cached| cached.hash);
+
+            if hash.is_none() || hash != cached_hash {
+                changed_files.insert(file.clone());
+            }
+        }
+
+        self.files.retain(|file, _| !changed_files.contains(file));
+        self.calls.retain(|(from, to)| {
+            !changed_files.contains(&from.uri) && !changed_files.contains(&to.uri)
+        });
+
+        changed_files
+    }
+
+    pub(crate) fn insert_symbols(&mut self, file: &Url, symbols: Vec<DocumentSymbol>) {
+        // files that can't be read are always treated as changed, so their hash doesn't matter
+        let hash = hash_file(file).unwrap_or_default();
+
+        self.files
+            .insert(file.clone(), CachedFile { hash, symbols });
+    }
+
+    /// Document symbols of each file
+    pub(crate) fn symbols(&self) -> impl Iterator<Item = (&Url, &[DocumentSymbol])> {
+        self.files
+            .iter()
+            .map(|(file, cached)| (file, cached.symbols.as_slice()))
+    }
+
+    /// Callable definitions of each file, with the symbol kinds of the cached options
+    pub(crate) fn definitions(&self) -> Vec<(Url, DocumentSymbol)> {
+        let symbol_kinds = self
+            .options
+            .as_ref()
+            .map_or(&[][..], |options| &options.symbol_kinds);
+
+        let mut definitions = vec![];
+        for (file, symbols) in self.symbols() {
+            update_exact_definitions(symbols.to_vec(), file, symbol_kinds, &mut definitions);
+        }
+
+        definitions
+    }
+
+    pub(crate) fn calls(&self) -> &Vec<(CallHierarchyItem, CallHierarchyItem)> {
+        &self.calls
+    }
+
+    pub(crate) fn insert_calls(&mut self, calls: Vec<(CallHierarchyItem, CallHierarchyItem)>) {
+        self.calls.extend(calls);
+    }
+}
+
+fn cache_dir(project_path: &Path) -> PathBuf {
+    project_path.join(CACHE_DIR)
+}
+
+fn hash_file(file: &Url) -> Option<u64> {
+    let content = fs::read(file.to_file_path().ok()?).ok()?;
+
+    Some(fnv1a(&content))
+}
+
+/// 64 bit FNV-1a hash, which unlike the std hashers is stable across Rust releases
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, fs};
+
+    use lsp_types::{SymbolKind, Url};
+
+    use crate::{test_util::item, CallGraphOptions};
+
+    use super::{fnv1a, AnalysisCache};
+
+    #[test]
+    fn test_fnv1a() {
+        // reference values of the FNV-1a 64 bit test suite
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn test_invalidate() {
+        let dir = std::env::temp_dir().join(format!("code_depth_cache_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let [a, b, c] = ["a.rs", "b.rs", "c.rs"].map(|name| {
+            let path = dir.join(name);
+            fs::write(&path, name).unwrap();
+
+            Url::from_file_path(path).unwrap()
+        });
+        let files = HashSet::from([a.clone(), b.clone(), c.clone()]);
+        let options = CallGraphOptions::default();
+
+        let mut cache = AnalysisCache::default();
+        assert_eq!(cache.invalidate(&files, &options), files);
+
+        for file in &files {
+            cache.insert_symbols(file, vec![]);
+        }
+        let f = item("f", SymbolKind::FUNCTION, a.as_str());
+        let g = item("g", SymbolKind::FUNCTION, b.as_str());
+        let h = item("h", SymbolKind::FUNCTION, c.as_str());
+        let k = item("k", SymbolKind::FUNCTION, c.as_str());
+        cache.insert_calls(vec![(f, g.clone()), (g, h.clone()), (h.clone(), k.clone())]);
+
+        assert!(cache.invalidate(&files, &options).is_empty());
+        assert_eq!(cache.calls().len(), 3);
+
+        // calls from and to the changed file are dropped, the callers get re-queried with it
+        fs::write(dir.join("b.rs"), "changed").unwrap();
+
+        assert_eq!(cache.invalidate(&files, &options), HashSet::from([b]));
+        assert_eq!(cache.calls(), &vec![(h, k)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+};
+
+use lsp_types::{CallHierarchyItem, Url};
+
+use crate::{
+    entry::{get_definition_head, has_word, is_lib_crate, is_public_rust_item, strip_comments},
+    language::Language,
+    symbol_arena::{SymbolArena, SymbolId},
+};
+
+/// Which unreachable functions to leave out of the report, since they may be called from
+/// outside of the project
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeadCodeOptions {
+    /// Trait impl methods (Rust), or methods annotated with `@Override` (Java)
+    pub exclude_trait_impls: bool,
+    /// Public items of lib crates (Rust), or public methods (Java)
+    pub exclude_exported: bool,
+}
+
+/// Get all project functions in the arena that aren't `reached` from any entry point, grouped
+/// by their file relative to the project root
+pub fn find_unreachable(
+    arena: &SymbolArena,
+    root: &Url,
+    reached: &HashSet<SymbolId>,
+    language: Language,
+    options: DeadCodeOptions,
+) -> BTreeMap<String, Vec<SymbolId>> {
+    let mut sources: HashMap<Url, Option<String>> = HashMap::new();
+
+    let mut unreachable: BTreeMap<String, Vec<SymbolId>> = BTreeMap::new();
+    for id in arena.ids().filter(|id| !reached.contains(id)) {
+        let item = arena.item(id);
+
+        // external items are only leaves, they can't be dead project code
+        let Some(relative_path) = item.uri.as_str().strip_prefix(root.as_str()) else {
+            continue;
+        };
+
+        if options.exclude_trait_impls || options.exclude_exported {
+            let source = sources.entry(item.uri.clone()).or_insert_with(|| {
+                let path = item.uri.to_file_path().ok()?;
+
+                fs::read_to_string(path).ok()
+            });
+
+            let source = source.as_deref().unwrap_or_default();
+
+            if (options.exclude_trait_impls && is_trait_impl(item, source, language))
+                || (options.exclude_exported && is_exported(item, source, language))
+            {
+                continue;
+            }
+        }
+
+        unreachable
+            .entry(relative_path.to_string())
+            .or_default()
+            .push(id);
+    }
+
+    unreachable
+}
+
+fn is_trait_impl(item: &CallHierarchyItem, source: &str, language: Language) -> bool {
+    match language {
+        Language::Rust => get_enclosing_block(source, item)
+            .is_some_and(|block| block.starts_with("impl") && block.contains(" for ")),
+        Language::Java | Language::Kotlin => {
+            let head = strip_comments(&get_definition_head(source, item));
+
+            head.contains("@Override") || has_word(&head, "override")
+        }
+        _ => false,
+    }
+}
+
+fn is_exported(item: &CallHierarchyItem, source: &str, language: Language) -> bool {
+    let head = strip_comments(&get_definition_head(source, item));
+
+    match language {
+        Language::Rust => {
+            let in_lib = item
+                .uri
+                .to_file_path()
+                .is_ok_and(|path| is_lib_crate(&path) && !path.ends_with("src/main.rs"));
+
+            in_lib && is_public_rust_item(item, &head)
+        }
+        Language::Java | Language::TypeScript => {
+            has_word(&head, "public") || has_word(&head, "export")
+        }
+        _ => false,
+    }
+}
+
+/// Get the first line above the definition that is indented less than it, which is
+/// the header of the block containing it (e.g. `impl Display for Foo {`)
+pub(crate) fn get_enclosing_block<'a>(
+    source: &'a str,
+    item: &CallHierarchyItem,
+) -> Option<&'a str> {
+    let lines = source.lines().collect::<Vec<_>>();
+    let line = lines.get(item.selection_range.start.line as usize)?;
+    let indent = line.len() - line.trim_start().len();
+
+    lines
+        .iter()
+        .take(item.selection_range.start.line as usize)
+        .rev()
+        .filter(|line| !line.trim().is_empty())
+        .find(|line| line.len() - line.trim_start().len() < indent)
+        .map(|line| line.trim_start())
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use super::{is_exported, is_trait_impl};
+    use crate::{language::Language, test_util::item_at};
+
+    const LIB: &str = "file:///project/src/lib.rs";
+
+    #[test]
+    fn test_is_trait_impl() {
+        let source = "impl Display for Foo {\n    fn fmt(&self) {\n\n        todo!()\n    }\n}\n\nimpl Foo {\n    fn new() {}\n}\n";
+
+        assert!(is_trait_impl(
+            &item_at("f", SymbolKind::METHOD, LIB, 1, 7),
+            source,
+            Language::Rust
+        ));
+        assert!(!is_trait_impl(
+            &item_at("f", SymbolKind::METHOD, LIB, 8, 7),
+            source,
+            Language::Rust
+        ));
+
+        let source = "class Foo implements Bar {\n    @Override\n    public void run() {}\n}\n";
+        assert!(is_trait_impl(
+            &item_at("f", SymbolKind::METHOD, LIB, 2, 16),
+            source,
+            Language::Java
+        ));
+    }
+
+    #[test]
+    fn test_is_exported() {
+        let source = "class Foo {\n    // public until v2\n    void run() {}\n    public void stop() {}\n}\n";
+
+        assert!(!is_exported(
+            &item_at("f", SymbolKind::METHOD, LIB, 2, 9),
+            source,
+            Language::Java
+        ));
+        assert!(is_exported(
+            &item_at("f", SymbolKind::METHOD, LIB, 3, 16),
+            source,
+            Language::Java
+        ));
+    }
+}
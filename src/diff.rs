@@ -0,0 +1,218 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    error::Error,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use log::{debug, warn};
+use serde::Serialize;
+
+use crate::Depths;
+
+/// Analysis results of a single revision, with items identified by their names
+pub struct Snapshot {
+    pub depths: Depths<String>,
+    pub problems: HashSet<String>,
+    pub calls: HashSet<(String, String)>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct DepthChange {
+    pub base: usize,
+    pub head: usize,
+    /// Positive when the item got deeper
+    pub delta: isize,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SnapshotDiff {
+    pub depth_changes: BTreeMap<String, DepthChange>,
+    pub new_problems: BTreeSet<String>,
+    pub resolved_problems: BTreeSet<String>,
+    pub added_calls: BTreeSet<(String, String)>,
+    pub removed_calls: BTreeSet<(String, String)>,
+}
+
+/// Compare the results of two revisions. The depth of an item is its shortest path from a root.
+pub fn diff_snapshots(base: &Snapshot, head: &Snapshot) -> SnapshotDiff {
+    let base_depths = get_min_depths(&base.depths);
+    let head_depths = get_min_depths(&head.depths);
+
+    let depth_changes = base_depths
+        .iter()
+        .filter_map(|(item, &base_depth)| {
+            let &head_depth = head_depths.get(item)?;
+
+            (base_depth != head_depth).then(|| {
+                (
+                    item.to_string(),
+                    DepthChange {
+                        base: base_depth,
+                        head: head_depth,
+                        delta: head_depth as isize - base_depth as isize,
+                    },
+                )
+            })
+        })
+        .collect();
+
+    SnapshotDiff {
+        depth_changes,
+        new_problems: head.problems.difference(&base.problems).cloned().collect(),
+        resolved_problems: base.problems.difference(&head.problems).cloned().collect(),
+        added_calls: head.calls.difference(&base.calls).cloned().collect(),
+        removed_calls: base.calls.difference(&head.calls).cloned().collect(),
+    }
+}
+
+fn get_min_depths(depths: &Depths<String>) -> HashMap<&str, usize> {
+    depths
+        .iter()
+        .filter_map(|(item, paths)| {
+            let depth = paths.iter().map(|path| path.depth).min()?;
+
+            Some((item.as_str(), depth))
+        })
+        .collect()
+}
+
+/// A detached git worktree of some revision, removed when dropped
+pub struct Worktree {
+    repo: PathBuf,
+    path: PathBuf,
+}
+
+impl Worktree {
+    /// Check out `rev` of the repository containing `repo_path` into a temporary directory
+    pub fn add(repo_path: &Path, rev: &str) -> Result<Self, Box<dyn Error>> {
+        let repo = PathBuf::from(git(repo_path, &["rev-parse", "--show-toplevel"])?.trim());
+
+        let dir_name = format!(
+            "code_depth_{}_{}",
+            std::process::id(),
+            rev.replace(|c: char| !c.is_ascii_alphanumeric(), "_")
+        );
+        let path = std::env::temp_dir().join(dir_name);
+
+        git(
+            &repo,
+            &[
+                "worktree",
+                "add",
+                "--detach",
+                path.to_str().ok_or("invalid worktree path")?,
+                rev,
+            ],
+        )?;
+
+        Ok(Self { repo, path })
+    }
+
+    /// Get the path inside the worktree matching `path` inside the original repository
+    pub fn translate(&self, path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        let repo = self.repo.canonicalize()?;
+        let relative_path = path.strip_prefix(repo)?;
+
+        Ok(self.path.join(relative_path))
+    }
+}
+
+impl Drop for Worktree {
+    fn drop(&mut self) {
+        let Some(path) = self.path.to_str() else {
+            return;
+        };
+
+        if let Err(e) = git(&self.repo, &["worktree", "remove", "--force", path]) {
+            warn!("failed to remove worktree {:?}: {}", self.path, e);
+        }
+    }
+}
+
+fn git(dir: &Path, args: &[&str]) -> Result<String, Box<dyn Error>> {
+    debug!("running git {:?} in {:?}", args, dir);
+
+    let output = Command::new("git").arg("-C").arg(dir).args(args).output()?;
+
+    if !output.status.success() {
+        return Err(format!(
+            "git {:?} failed: {}",
+            args,
+            String::from_utf8_lossy(&output.stderr)
+        )
+        .into());
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::CallPath;
+
+    use super::{diff_snapshots, DepthChange, Snapshot};
+
+    fn snapshot(calls: &[(&str, &str)], depths: &[(&str, usize)], problems: &[&str]) -> Snapshot {
+        Snapshot {
+            depths: depths
+                .iter()
+                .map(|(item, depth)| {
+                    let path = CallPath {
+                        depth: *depth,
+                        path: vec![item.to_string(); depth + 1],
+                    };
+
+                    (item.to_string(), vec![path])
+                })
+                .collect(),
+            problems: problems.iter().map(|p| p.to_string()).collect(),
+            calls: calls
+                .iter()
+                .map(|(s, t)| (s.to_string(), t.to_string()))
+                .collect::<HashSet<_>>(),
+        }
+    }
+
+    #[test]
+    fn test_diff_snapshots() {
+        let base = snapshot(
+            &[("main", "handle"), ("main", "util")],
+            &[("main", 0), ("handle", 1), ("util", 1)],
+            &["util"],
+        );
+        let head = snapshot(
+            &[("main", "parse"), ("parse", "handle"), ("main", "util")],
+            &[("main", 0), ("parse", 1), ("handle", 2), ("util", 1)],
+            &["handle"],
+        );
+
+        let diff = diff_snapshots(&base, &head);
+
+        assert_eq!(diff.depth_changes.len(), 1);
+        assert_eq!(
+            diff.depth_changes["handle"],
+            DepthChange {
+                base: 1,
+                head: 2,
+                delta: 1
+            }
+        );
+        assert_eq!(diff.new_problems, ["handle".to_string()].into());
+        assert_eq!(diff.resolved_problems, ["util".to_string()].into());
+        assert_eq!(
+            diff.added_calls,
+            [
+                ("main".to_string(), "parse".to_string()),
+                ("parse".to_string(), "handle".to_string())
+            ]
+            .into()
+        );
+        assert_eq!(
+            diff.removed_calls,
+            [("main".to_string(), "handle".to_string())].into()
+        );
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use crate::graph_util::{get_topological_order, CallGraph};
+
+/// Dependency structure matrix of calls between groups (files / modules / packages).
+///
+/// Rows and columns are the groups in topological order (callers first), and each cell holds
+/// the number of calls from the column's group to the row's group. Calls against the
+/// topological order (back edges, part of a cycle) end up above the diagonal.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Dsm {
+    pub groups: Vec<String>,
+    pub counts: Vec<Vec<usize>>,
+}
+
+impl Dsm {
+    pub fn new(group_calls: &[(String, String)]) -> Self {
+        let groups = get_topological_order(&CallGraph::new(group_calls));
+
+        let index = groups
+            .iter()
+            .enumerate()
+            .map(|(i, group)| (group.as_str(), i))
+            .collect::<HashMap<_, _>>();
+
+        let mut counts = vec![vec![0; groups.len()]; groups.len()];
+        for (caller, callee) in group_calls {
+            counts[index[callee.as_str()]][index[caller.as_str()]] += 1;
+        }
+
+        Self { groups, counts }
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::new();
+
+        let header = self.groups.iter().map(|group| escape_csv(group));
+        csv.push_str(
+            &std::iter::once(String::new())
+                .chain(header)
+                .collect::<Vec<_>>()
+                .join(","),
+        );
+        csv.push('\n');
+
+        for (group, row) in self.groups.iter().zip(&self.counts) {
+            let cells = row.iter().map(|count| count.to_string());
+            csv.push_str(
+                &std::iter::once(escape_csv(group))
+                    .chain(cells)
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+            csv.push('\n');
+        }
+
+        csv
+    }
+
+    /// Render the matrix as a standalone html heatmap, with back edges in red
+    pub fn to_html(&self) -> String {
+        let max_count = self
+            .counts
+            .iter()
+            .flatten()
+            .copied()
+            .max()
+            .unwrap_or(0)
+            .max(1);
+
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>DSM</title>\n\
+             <style>\n\
+             table { border-collapse: collapse; font-family: monospace; font-size: 12px; }\n\
+             td, th { border: 1px solid #ccc; padding: 2px 6px; text-align: center; }\n\
+             th.row { text-align: left; }\n\
+             td.diagonal { background: #eee; }\n\
+             </style>\n</head>\n<body>\n<table>\n<tr><th></th>",
+        );
+
+        for i in 0..self.groups.len() {
+            html.push_str(&format!(
+                "<th title=\"{}\">{}</th>",
+                escape_html(&self.groups[i]),
+                i + 1
+            ));
+        }
+        html.push_str("</tr>\n");
+
+        for (i, (group, row)) in self.groups.iter().zip(&self.counts).enumerate() {
+            html.push_str(&format!(
+                "<tr><th class=\"row\">{} {}</th>",
+                i + 1,
+                escape_html(group)
+            ));
+
+            for (j, &count) in row.iter().enumerate() {
+                if i == j {
+                    html.push_str(&format!(
+                        "<td class=\"diagonal\">{}</td>",
+                        display_count(count)
+                    ));
+                    continue;
+                }
+
+                // back edges (above the diagonal) in red, regular calls in blue
+                let hue = if j > i { 0 } else { 210 };
+                let lightness = 100 - 50 * count / max_count;
+
+                html.push_str(&format!(
+                    "<td style=\"background: hsl({}, 80%, {}%)\">{}</td>",
+                    hue,
+                    lightness,
+                    display_count(count)
+                ));
+            }
+
+            html.push_str("</tr>\n");
+        }
+
+        html.push_str("</table>\n</body>\n</html>\n");
+
+        html
+    }
+}
+
+fn display_count(count: usize) -> String {
+    if count == 0 {
+        String::new()
+    } else {
+        count.to_string()
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dsm;
+
+    #[test]
+    fn test_dsm() {
+        let calls = [
+            ("api", "service"),
+            ("api", "service"),
+            ("service", "db"),
+            ("db", "service"),
+            ("api", "api"),
+        ]
+        .iter()
+        .map(|(s, t)| (s.to_string(), t.to_string()))
+        .collect::<Vec<_>>();
+
+        let dsm = Dsm::new(&calls);
+
+        assert_eq!(dsm.groups, ["api", "service", "db"]);
+        assert_eq!(dsm.counts, [[1, 0, 0], [2, 0, 1], [0, 1, 0]]);
+        assert_eq!(
+            dsm.to_csv(),
+            ",api,service,db\napi,1,0,0\nservice,2,0,1\ndb,0,1,0\n"
+        );
+    }
+}
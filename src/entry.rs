@@ -0,0 +1,381 @@
+use std::{collections::HashMap, fs, path::Path, str::FromStr};
+
+use lsp_types::{CallHierarchyItem, SymbolKind, Url};
+use regex::Regex;
+use serde::Serialize;
+
+use crate::{
+    build_call_hierarchy_item_name,
+    language::Language,
+    symbol_arena::{SymbolArena, SymbolId},
+};
+
+/// Java servlet methods called by the servlet container
+const SERVLET_HANDLERS: [&str; 8] = [
+    "service",
+    "doGet",
+    "doPost",
+    "doPut",
+    "doDelete",
+    "doHead",
+    "doOptions",
+    "doTrace",
+];
+
+/// Why a function is an entry point
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryKind {
+    /// Matched an `--entry` pattern
+    Declared,
+    Main,
+    TokioMain,
+    Test,
+    /// Public item of a library crate
+    PublicApi,
+    ServletHandler,
+}
+
+/// User-declared entry point, given as either:
+/// - a regex over the item name (e.g. `src/main.rs:main`)
+/// - a symbol kind and a path prefix relative to the project root (e.g. `Method:src/api`)
+#[derive(Debug, Clone)]
+pub enum EntryPattern {
+    Name(Regex),
+    KindPath(SymbolKind, String),
+}
+
+impl FromStr for EntryPattern {
+    type Err = String;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if let Some((kind, path)) = pattern.split_once(':') {
+            if let Ok(kind) = SymbolKind::try_from(kind) {
+                // paths are relative to the project root, with or without a leading slash
+                let path = path.trim_start_matches('/').to_string();
+
+                return Ok(Self::KindPath(kind, path));
+            }
+        }
+
+        Regex::new(pattern)
+            .map(Self::Name)
+            .map_err(|e| format!("invalid entry pattern '{}': {}", pattern, e))
+    }
+}
+
+impl EntryPattern {
+    pub fn matches(&self, item: &CallHierarchyItem, root: &Url) -> bool {
+        match self {
+            Self::Name(re) => re.is_match(&build_call_hierarchy_item_name(item, root)),
+            Self::KindPath(kind, path) => {
+                item.kind == *kind
+                    && item
+                        .uri
+                        .as_str()
+                        .strip_prefix(root.as_str())
+                        .is_some_and(|relative| {
+                            relative.trim_start_matches('/').starts_with(path.as_str())
+                        })
+            }
+        }
+    }
+}
+
+/// Get all interned items matching any of the patterns
+pub fn find_entries(arena: &SymbolArena, root: &Url, patterns: &[EntryPattern]) -> Vec<SymbolId> {
+    arena
+        .ids()
+        .filter(|&id| {
+            patterns
+                .iter()
+                .any(|pattern| pattern.matches(arena.item(id), root))
+        })
+        .collect()
+}
+
+/// Find entry points among all interned items, based on their names, details and the source
+/// code preceding their names (attributes, annotations and modifiers)
+pub fn detect_entries(arena: &SymbolArena, language: Language) -> Vec<(SymbolId, EntryKind)> {
+    let mut sources: HashMap<Url, Option<String>> = HashMap::new();
+
+    arena
+        .ids()
+        .filter_map(|id| {
+            let item = arena.item(id);
+            let path = item.uri.to_file_path().ok()?;
+
+            let source = sources
+                .entry(item.uri.clone())
+                .or_insert_with(|| fs::read_to_string(&path).ok())
+                .as_deref()?;
+
+            let head = strip_comments(&get_definition_head(source, item));
+
+            let kind = match language {
+                Language::Rust => detect_rust_entry(item, &head, &path),
+                Language::Java => detect_java_entry(item, &head, source),
+                _ => None,
+            }?;
+
+            Some((id, kind))
+        })
+        .collect()
+}
+
+/// Get the source of a definition up to its name, including the attributes / annotations and
+/// comments right above it (some servers don't include them in the definition range)
+pub(crate) fn get_definition_head(source: &str, item: &CallHierarchyItem) -> String {
+    let lines = source.lines().collect::<Vec<_>>();
+
+    let mut start = item.range.start.line as usize;
+    while start > 0 {
+        let line = lines.get(start - 1).map_or("", |line| line.trim_start());
+
+        if line.starts_with("#[") || line.starts_with('@') || line.starts_with("//") {
+            start -= 1;
+        } else {
+            break;
+        }
+    }
+
+    let end = item.selection_range.start;
+    let mut head = lines
+        .iter()
+        .take(end.line as usize)
+        .skip(start)
+        .fold(String::new(), |head, line| head + line + "\n");
+
+    if let Some(line) = lines.get(end.line as usize) {
+        head.extend(line.chars().take(end.character as usize));
+    }
+
+    head
+}
+
+/// Remove `//` and `/* */` comments, so that commented out attributes and modifiers don't match.
+/// Comment markers inside string literals are kept.
+pub(crate) fn strip_comments(head: &str) -> String {
+    let mut code = String::new();
+    let mut chars = head.chars().peekable();
+    let mut in_string = false;
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            code.push(c);
+
+            match c {
+                '\\' => code.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+            code.push(c);
+        } else if c == '/' && chars.peek() == Some(&'/') {
+            // keep the newline ending the comment
+            while chars.next_if(|&c| c != '\n').is_some() {}
+        } else if c == '/' && chars.peek() == Some(&'*') {
+            chars.next();
+
+            let mut previous = ' ';
+            for c in chars.by_ref() {
+                if previous == '*' && c == '/' {
+                    break;
+                }
+
+                previous = c;
+            }
+
+            code.push(' ');
+        } else {
+            code.push(c);
+        }
+    }
+
+    code
+}
+
+/// Whether `head` contains `word` as a whole word, e.g. a modifier
+pub(crate) fn has_word(head: &str, word: &str) -> bool {
+    head.split(|c: char| !c.is_alphanumeric() && c != '_')
+        .any(|w| w == word)
+}
+
+fn detect_rust_entry(item: &CallHierarchyItem, head: &str, path: &Path) -> Option<EntryKind> {
+    let is_bin = path.ends_with("src/main.rs") || path.parent()?.ends_with("src/bin");
+
+    if head.contains("#[test]") || head.contains("#[tokio::test") {
+        Some(EntryKind::Test)
+    } else if head.contains("#[tokio::main") {
+        Some(EntryKind::TokioMain)
+    } else if is_bin && item.name == "main" && item.kind == SymbolKind::FUNCTION {
+        Some(EntryKind::Main)
+    } else if !is_bin && is_public_rust_item(item, head) && is_lib_crate(path) {
+        Some(EntryKind::PublicApi)
+    } else {
+        None
+    }
+}
+
+pub(crate) fn is_public_rust_item(item: &CallHierarchyItem, head: &str) -> bool {
+    // rust-analyzer puts the signature in the detail, but not necessarily the visibility
+    let signature = item.detail.as_deref().unwrap_or_default();
+
+    // "pub(crate)" and friends are a single word, so only plain "pub" matches
+    [signature, head]
+        .iter()
+        .any(|text| text.split_whitespace().any(|word| word == "pub"))
+}
+
+/// Whether the crate containing `path` has a `src/lib.rs`
+pub(crate) fn is_lib_crate(path: &Path) -> bool {
+    path.ancestors()
+        .find(|dir| dir.join("Cargo.toml").is_file())
+        .is_some_and(|dir| dir.join("src").join("lib.rs").is_file())
+}
+
+fn detect_java_entry(item: &CallHierarchyItem, head: &str, source: &str) -> Option<EntryKind> {
+    // jdtls names methods with their parameter types, e.g. "main(String[])"
+    let name = item.name.split('(').next()?;
+
+    if head.contains("@Test") || head.contains("@ParameterizedTest") {
+        Some(EntryKind::Test)
+    } else if name == "main" && has_word(head, "public") && has_word(head, "static") {
+        Some(EntryKind::Main)
+    } else if SERVLET_HANDLERS.contains(&name) && source.contains("extends HttpServlet") {
+        Some(EntryKind::ServletHandler)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use lsp_types::{SymbolKind, Url};
+
+    use super::{
+        detect_java_entry, detect_rust_entry, get_definition_head, strip_comments, EntryKind,
+        EntryPattern,
+    };
+    use crate::test_util::{item, item_at};
+
+    const MAIN: &str = "file:///project/src/main.rs";
+
+    #[test]
+    fn test_entry_patterns() {
+        // like the project url, without a trailing slash
+        let root = Url::from_file_path("/project").unwrap();
+        let main = item("main", SymbolKind::FUNCTION, MAIN);
+        let handler = item(
+            "get_user",
+            SymbolKind::METHOD,
+            "file:///project/src/api/users.rs",
+        );
+
+        let name = "src/main.rs:main$".parse::<EntryPattern>().unwrap();
+        assert!(name.matches(&main, &root));
+        assert!(!name.matches(&handler, &root));
+
+        let kind_path = "Method:src/api".parse::<EntryPattern>().unwrap();
+        assert!(kind_path.matches(&handler, &root));
+        assert!(!kind_path.matches(&main, &root));
+        assert!("Method:/src/api"
+            .parse::<EntryPattern>()
+            .unwrap()
+            .matches(&handler, &root));
+
+        assert!("Function:(".parse::<EntryPattern>().is_ok());
+        assert!("src/main.rs:(".parse::<EntryPattern>().is_err());
+    }
+
+    #[test]
+    fn test_get_definition_head() {
+        let source = "fn helper() {}\n\n/// docs\n#[test]\nfn check() {\n}\n";
+        let check = item_at("check", SymbolKind::FUNCTION, MAIN, 4, 3);
+
+        assert_eq!(
+            get_definition_head(source, &check),
+            "/// docs\n#[test]\nfn "
+        );
+    }
+
+    #[test]
+    fn test_detect_rust_entry() {
+        let main = item("main", SymbolKind::FUNCTION, MAIN);
+        let bin = Path::new("/project/src/main.rs");
+
+        assert_eq!(detect_rust_entry(&main, "fn ", bin), Some(EntryKind::Main));
+        assert_eq!(
+            detect_rust_entry(&main, "#[tokio::main]\nasync fn ", bin),
+            Some(EntryKind::TokioMain)
+        );
+        assert_eq!(
+            detect_rust_entry(&main, "#[test]\nfn ", bin),
+            Some(EntryKind::Test)
+        );
+
+        let helper = item("helper", SymbolKind::FUNCTION, MAIN);
+        assert_eq!(detect_rust_entry(&helper, "pub fn ", bin), None);
+
+        // commented out attributes don't count
+        let head = strip_comments("// #[test]\n/* #[tokio::main] */\nfn ");
+        assert_eq!(detect_rust_entry(&helper, &head, bin), None);
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(
+            strip_comments("/// docs\n// #[test]\n#[doc = \"//\"] /* old */fn "),
+            "\n\n#[doc = \"//\"]  fn "
+        );
+    }
+
+    #[test]
+    fn test_detect_java_entry() {
+        let main = item(
+            "main(String[])",
+            SymbolKind::METHOD,
+            "file:///project/src/App.java",
+        );
+        assert_eq!(
+            detect_java_entry(&main, "public static void ", ""),
+            Some(EntryKind::Main)
+        );
+
+        assert_eq!(
+            detect_java_entry(&main, &strip_comments("/* public */ static void "), ""),
+            None
+        );
+
+        let test = item(
+            "parses()",
+            SymbolKind::METHOD,
+            "file:///project/src/AppTest.java",
+        );
+        assert_eq!(
+            detect_java_entry(&test, "@Test\nvoid ", ""),
+            Some(EntryKind::Test)
+        );
+
+        let handler = item(
+            "doGet(HttpServletRequest, HttpServletResponse)",
+            SymbolKind::METHOD,
+            "file:///project/src/Api.java",
+        );
+        assert_eq!(
+            detect_java_entry(
+                &handler,
+                "protected void ",
+                "class Api extends HttpServlet {"
+            ),
+            Some(EntryKind::ServletHandler)
+        );
+        assert_eq!(
+            detect_java_entry(&handler, "protected void ", "class Api {"),
+            None
+        );
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use crate::CallPath;
+
+/// Why an item is reached at different depths
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation<T> {
+    pub item: T,
+    /// Shortest path to the item
+    pub shorter: CallPath<T>,
+    /// Shortest path to the item with a different depth than `shorter`
+    pub longer: CallPath<T>,
+    /// Number of hops both paths share from their start, 0 if they start at different roots
+    pub shared_hops: usize,
+    /// Direct callers of the item on paths of each depth
+    pub callers_by_depth: BTreeMap<usize, Vec<T>>,
+}
+
+/// Find the minimal pair of paths of different depths to `item`, out of its shortest path from
+/// each root and its `longest` path, if known. Returns `None` if all paths have the same depth.
+pub fn explain<T: Clone + PartialEq>(
+    item: &T,
+    paths: &[CallPath<T>],
+    longest: Option<&CallPath<T>>,
+) -> Option<Explanation<T>> {
+    let paths = paths.iter().chain(longest).collect::<Vec<_>>();
+
+    let shorter = *paths.iter().min_by_key(|path| path.depth)?;
+    let longer = *paths
+        .iter()
+        .filter(|path| path.depth != shorter.depth)
+        .min_by_key(|path| path.depth)?;
+
+    let shared_hops = shorter
+        .path
+        .iter()
+        .zip(longer.path.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut callers_by_depth: BTreeMap<usize, Vec<T>> = BTreeMap::new();
+    for path in &paths {
+        // a root has no callers
+        let Some(caller) = path.path.len().checked_sub(2).map(|i| &path.path[i]) else {
+            continue;
+        };
+
+        let callers = callers_by_depth.entry(path.depth).or_default();
+        if !callers.contains(caller) {
+            callers.push(caller.clone());
+        }
+    }
+
+    Some(Explanation {
+        item: item.clone(),
+        shorter: shorter.clone(),
+        longer: longer.clone(),
+        shared_hops,
+        callers_by_depth,
+    })
+}
+
+/// Render both paths side by side as trees, marking the hops where they diverge
+pub fn render(explanation: &Explanation<String>) -> String {
+    let column = |path: &[String], title: String| {
+        std::iter::once(title)
+            .chain(path.iter().enumerate().map(|(depth, hop)| {
+                let branch = if depth == 0 { "" } else { "└─ " };
+                let marker = if depth == explanation.shared_hops {
+                    " *"
+                } else {
+                    ""
+                };
+
+                format!(
+                    "{}{}{}{}",
+                    "   ".repeat(depth.saturating_sub(1)),
+                    branch,
+                    hop,
+                    marker
+                )
+            }))
+            .collect::<Vec<_>>()
+    };
+
+    let left = column(
+        &explanation.shorter.path,
+        format!("depth {}", explanation.shorter.depth),
+    );
+    let right = column(
+        &explanation.longer.path,
+        format!("depth {}", explanation.longer.depth),
+    );
+
+    let width = left
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0);
+
+    let mut output = format!("{} is reached at different depths\n\n", explanation.item);
+
+    for i in 0..left.len().max(right.len()) {
+        let l = left.get(i).map_or("", |line| line.as_str());
+        let r = right.get(i).map_or("", |line| line.as_str());
+
+        let padding = width - l.chars().count();
+        output.push_str(format!("{}{}  |  {}", l, " ".repeat(padding), r).trim_end());
+        output.push('\n');
+    }
+
+    output.push('\n');
+    if explanation.shared_hops == 0 {
+        output.push_str("* paths start from different roots\n");
+    } else {
+        output.push_str(&format!(
+            "* paths diverge after {}\n",
+            explanation.shorter.path[explanation.shared_hops - 1]
+        ));
+    }
+
+    output.push('\n');
+    for (depth, callers) in &explanation.callers_by_depth {
+        output.push_str(&format!(
+            "callers at depth {}: {}\n",
+            depth,
+            callers.join(", ")
+        ));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::CallPath;
+
+    use super::{explain, render};
+
+    fn path(hops: &[&str]) -> CallPath<String> {
+        CallPath {
+            depth: hops.len() - 1,
+            path: names(hops),
+        }
+    }
+
+    fn names(hops: &[&str]) -> Vec<String> {
+        hops.iter().map(|hop| hop.to_string()).collect()
+    }
+
+    #[test]
+    fn test_explain() {
+        // the shortest path from each root
+        let item = "log".to_string();
+        let paths = [
+            path(&["worker", "run", "step", "log"]),
+            path(&["main", "log"]),
+            path(&["cli", "run", "log"]),
+        ];
+
+        let explanation = explain(&item, &paths, None).unwrap();
+
+        assert_eq!(explanation.shorter, path(&["main", "log"]));
+        assert_eq!(explanation.longer, path(&["cli", "run", "log"]));
+        assert_eq!(explanation.shared_hops, 0);
+        assert_eq!(
+            explanation.callers_by_depth,
+            BTreeMap::from([
+                (1, names(&["main"])),
+                (2, names(&["run"])),
+                (3, names(&["step"]))
+            ])
+        );
+
+        assert_eq!(explain(&item, &[path(&["main", "log"])], None), None);
+
+        // calls inside a recursive cluster don't add to the depth
+        let cluster_path = CallPath {
+            depth: 1,
+            path: names(&["main", "parse", "parse_expr", "log"]),
+        };
+        assert_eq!(
+            explain(&item, &[path(&["worker", "log"]), cluster_path], None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_explain_max_depth() {
+        // with a single root, only the longest path can disagree with the shortest one
+        let item = "log".to_string();
+        let paths = [path(&["main", "log"])];
+        let longest = path(&["main", "run", "log"]);
+
+        let explanation = explain(&item, &paths, Some(&longest)).unwrap();
+
+        assert_eq!(explanation.shorter, path(&["main", "log"]));
+        assert_eq!(explanation.longer, longest);
+        assert_eq!(explanation.shared_hops, 1);
+
+        assert_eq!(
+            render(&explanation),
+            "log is reached at different depths\n\
+             \n\
+             depth 1   |  depth 2\n\
+             main      |  main\n\
+             └─ log *  |  └─ run *\n\
+             \x20         |     └─ log\n\
+             \n\
+             * paths diverge after main\n\
+             \n\
+             callers at depth 1: main\n\
+             callers at depth 2: run\n"
+        );
+
+        assert_eq!(explain(&item, &paths, Some(&path(&["main", "log"]))), None);
+    }
+}
@@ -0,0 +1,137 @@
+use lsp_types::Url;
+
+/// Get the name of the crate / package / jar that an item outside the project belongs to
+pub fn get_external_package_name(uri: &Url) -> String {
+    // jdtls serves class files from jars as 'jdt://contents/<jar>/<package>/<class>?...'
+    if uri.scheme() == "jdt" {
+        if let Some(jar) = uri.path_segments().and_then(|mut s| s.next()) {
+            return jar.to_string();
+        }
+    }
+
+    let segments = uri
+        .path_segments()
+        .map(|s| s.collect::<Vec<_>>())
+        .unwrap_or_default();
+
+    let segment_after = |marker: &[&str]| {
+        segments
+            .windows(marker.len() + 1)
+            .rev()
+            .find(|w| w[..marker.len()] == *marker)
+            .map(|w| w[marker.len()])
+    };
+
+    // rust standard library: .../rustlib/src/rust/library/<crate>/...
+    if let Some(krate) = segment_after(&["rust", "library"]) {
+        return krate.to_string();
+    }
+
+    // cargo registry: .../registry/src/<index>/<crate>-<version>/...
+    if let Some(index) = segments.iter().rposition(|&s| s == "registry") {
+        if segments.get(index + 1) == Some(&"src") {
+            if let Some(krate) = segments.get(index + 3) {
+                return strip_version(krate).to_string();
+            }
+        }
+    }
+
+    // cargo git dependencies: .../git/checkouts/<crate>-<hash>/<rev>/...
+    if let Some(checkout) = segment_after(&["git", "checkouts"]) {
+        return checkout
+            .rsplit_once('-')
+            .map_or(checkout, |(krate, _)| krate)
+            .to_string();
+    }
+
+    // maven: .../.m2/repository/<group>/<artifact>/<version>/<artifact>-<version>.jar
+    if let Some(index) = segments.iter().position(|&s| s == ".m2") {
+        if segments.len() > index + 4 {
+            return segments[segments.len() - 3].to_string();
+        }
+    }
+
+    // node: .../node_modules/<package> or .../node_modules/@<scope>/<package>
+    if let Some(index) = segments.iter().rposition(|&s| s == "node_modules") {
+        match (segments.get(index + 1), segments.get(index + 2)) {
+            (Some(scope), Some(package)) if scope.starts_with('@') => {
+                return format!("{}/{}", scope, package)
+            }
+            (Some(package), _) => return package.to_string(),
+            _ => {}
+        }
+    }
+
+    // fallback to the directory containing the item
+    segments
+        .iter()
+        .rev()
+        .nth(1)
+        .map_or_else(|| uri.to_string(), |dir| dir.to_string())
+}
+
+/// Strip a trailing '-<version>' from a cargo registry directory name
+fn strip_version(name: &str) -> &str {
+    name.match_indices('-')
+        .find(|(i, _)| {
+            name[i + 1..]
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_digit())
+        })
+        .map_or(name, |(i, _)| &name[..i])
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Url;
+
+    use super::get_external_package_name;
+
+    fn package(uri: &str) -> String {
+        get_external_package_name(&Url::parse(uri).unwrap())
+    }
+
+    #[test]
+    fn test_cargo_registry() {
+        assert_eq!(
+            package("file:///home/u/.cargo/registry/src/github.com-1ecc6299db9ec823/tokio-1.21.2/src/task/spawn.rs"),
+            "tokio"
+        );
+        assert_eq!(
+            package("file:///home/u/.cargo/registry/src/index.crates.io-6f17d22bba15001f/serde-json-1.0.87/src/lib.rs"),
+            "serde-json"
+        );
+    }
+
+    #[test]
+    fn test_rust_std() {
+        assert_eq!(
+            package("file:///home/u/.rustup/toolchains/stable-x86_64-unknown-linux-gnu/lib/rustlib/src/rust/library/core/src/fmt/builders.rs"),
+            "core"
+        );
+    }
+
+    #[test]
+    fn test_java() {
+        assert_eq!(
+            package("jdt://contents/junit-4.11.jar/org.junit/Assert.class?=sample/junit"),
+            "junit-4.11.jar"
+        );
+        assert_eq!(
+            package(
+                "file:///home/u/.m2/repository/org/slf4j/slf4j-api/1.7.36/slf4j-api-1.7.36.jar"
+            ),
+            "slf4j-api"
+        );
+    }
+
+    #[test]
+    fn test_node_modules() {
+        assert_eq!(
+            package("file:///p/node_modules/@types/node/fs.d.ts"),
+            "@types/node"
+        );
+        assert_eq!(package("file:///p/node_modules/lodash/map.js"), "lodash");
+    }
+}
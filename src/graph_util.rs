@@ -1,109 +1,1035 @@
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{HashMap, HashSet, VecDeque},
     fmt::Debug,
     hash::Hash,
 };
 
-use petgraph::Graph;
+use petgraph::{
+    algo::{dominators::simple_fast, greedy_feedback_arc_set, tarjan_scc},
+    graph::NodeIndex,
+    visit::EdgeRef,
+    Direction, Graph,
+};
+use serde::Serialize;
+
+/// Call graph built once from a list of edges, with each item interned as a node, and shared
+/// by all analyses
+pub struct CallGraph<T> {
+    graph: Graph<T, ()>,
+    nodes: HashMap<T, NodeIndex>,
+    /// Strongly connected components in reverse topological order, each sorted in order of
+    /// first appearance
+    sccs: Vec<Vec<NodeIndex>>,
+    /// Position in `sccs` of the component of each node
+    component: Vec<usize>,
+}
+
+impl<T> CallGraph<T>
+where
+    T: Clone + Hash + Eq,
+{
+    pub fn new(edges: &[(T, T)]) -> Self {
+        let mut nodes = HashMap::new();
+        let mut graph: Graph<T, ()> = Graph::new();
+
+        for (s, t) in edges {
+            let s = *nodes
+                .entry(s.clone())
+                .or_insert_with(|| graph.add_node(s.clone()));
+            let t = *nodes
+                .entry(t.clone())
+                .or_insert_with(|| graph.add_node(t.clone()));
+
+            graph.add_edge(s, t, ());
+        }
+
+        let mut sccs = tarjan_scc(&graph);
+
+        let mut component = vec![0; graph.node_count()];
+        for (i, scc) in sccs.iter_mut().enumerate() {
+            scc.sort();
+
+            for node in scc.iter() {
+                component[node.index()] = i;
+            }
+        }
+
+        Self {
+            graph,
+            nodes,
+            sccs,
+            component,
+        }
+    }
+
+    pub fn node(&self, item: &T) -> Option<NodeIndex> {
+        self.nodes.get(item).copied()
+    }
+
+    /// All nodes, in order of first appearance
+    pub fn nodes(&self) -> impl Iterator<Item = NodeIndex> {
+        self.graph.node_indices()
+    }
+
+    /// Distinct direct callees of `node`, in order of first appearance
+    pub fn callees(&self, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut callees = self.graph.neighbors(node).collect::<Vec<_>>();
+        callees.sort();
+        callees.dedup();
+
+        callees
+    }
+
+    /// Strongly connected components in reverse topological order (callees before callers),
+    /// each sorted in order of first appearance
+    pub fn sccs(&self) -> &[Vec<NodeIndex>] {
+        &self.sccs
+    }
+
+    /// Position in [`CallGraph::sccs`] of the component of `node`
+    pub fn component(&self, node: NodeIndex) -> usize {
+        self.component[node.index()]
+    }
+
+    /// One node from each component without incoming edges from other components (the
+    /// sources of the condensation DAG), in order of first appearance.
+    ///
+    /// This way recursive clusters at the top of the graph still get a root.
+    pub fn roots(&self) -> Vec<NodeIndex> {
+        let mut has_incoming = vec![false; self.sccs.len()];
+        for edge in self.graph.raw_edges() {
+            let (s, t) = (self.component(edge.source()), self.component(edge.target()));
+            if s != t {
+                has_incoming[t] = true;
+            }
+        }
+
+        let mut roots = self
+            .sccs
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| !has_incoming[i])
+            .map(|(_, scc)| scc[0])
+            .collect::<Vec<_>>();
+
+        roots.sort();
+
+        roots
+    }
+
+    /// Longest path from any of the roots to each node reachable from them, computed on the
+    /// condensation DAG. Calls inside a recursive cluster don't add to the depth, so all
+    /// of its members share the same depth. Each path is a real call chain: it enters a
+    /// cluster through the call that gives it its depth, then follows the shortest chain of
+    /// calls inside the cluster to the node.
+    pub fn longest_paths(&self, roots: &[NodeIndex]) -> Vec<(NodeIndex, CallPath<NodeIndex>)> {
+        let sccs = &self.sccs;
+        let component = &self.component;
+
+        let mut depths: Vec<Option<usize>> = vec![None; sccs.len()];
+        // the call used to enter each component on its longest path, without a caller for roots
+        let mut entries: Vec<Option<(Option<NodeIndex>, NodeIndex)>> = vec![None; sccs.len()];
+
+        for &root in roots {
+            depths[component[root.index()]] = Some(0);
+            entries[component[root.index()]] = Some((None, root));
+        }
+
+        // sccs are in reverse topological order, so all callers of a component
+        // are done before it
+        for (i, scc) in sccs.iter().enumerate().rev() {
+            let Some(depth) = depths[i] else {
+                continue;
+            };
+
+            for &node in scc {
+                for neighbor in self.graph.neighbors(node) {
+                    let j = component[neighbor.index()];
+
+                    if j != i && depths[j].is_none_or(|d| d < depth + 1) {
+                        depths[j] = Some(depth + 1);
+                        entries[j] = Some((Some(node), neighbor));
+                    }
+                }
+            }
+        }
+
+        // predecessor of each node on its path: the caller entering its component, or the
+        // previous node on the shortest chain of calls inside the component
+        let mut predecessors = HashMap::new();
+        for &(caller, entered) in entries.iter().flatten() {
+            if let Some(caller) = caller {
+                predecessors.insert(entered, caller);
+            }
+
+            let mut queue = VecDeque::from([entered]);
+            let mut visited = HashSet::from([entered]);
+            while let Some(node) = queue.pop_front() {
+                for neighbor in self.graph.neighbors(node) {
+                    if component[neighbor.index()] == component[node.index()]
+                        && visited.insert(neighbor)
+                    {
+                        predecessors.insert(neighbor, node);
+                        queue.push_back(neighbor);
+                    }
+                }
+            }
+        }
+
+        self.graph
+            .node_indices()
+            .filter_map(|node| {
+                let depth = depths[component[node.index()]]?;
+
+                let mut path = vec![node];
+                while let Some(&predecessor) = predecessors.get(path.last().unwrap()) {
+                    path.push(predecessor);
+                }
+
+                path.reverse();
+
+                Some((node, CallPath { depth, path }))
+            })
+            .collect()
+    }
+
+    /// Nodes reachable from any of the roots, including the roots
+    pub fn reachable(&self, roots: &[NodeIndex]) -> Vec<NodeIndex> {
+        let mut visited = vec![false; self.graph.node_count()];
+        let mut stack = roots.to_vec();
+        let mut reached = vec![];
+
+        while let Some(node) = stack.pop() {
+            if visited[node.index()] {
+                continue;
+            }
+
+            visited[node.index()] = true;
+            reached.push(node);
+            stack.extend(self.graph.neighbors(node));
+        }
+
+        reached
+    }
+
+    /// Nodes of the given entries, or the inferred roots if there are no entries
+    pub fn entry_roots(&self, entries: Option<&[T]>) -> Vec<NodeIndex> {
+        match entries {
+            Some(entries) => entries
+                .iter()
+                .filter_map(|entry| self.node(entry))
+                .collect(),
+            None => self.roots(),
+        }
+    }
+
+    /// Number of nodes each node dominates (excluding itself), in a single dominator tree with a
+    /// virtual root calling all roots. A node dominates another if every path from any root to
+    /// the other node goes through it.
+    pub fn dominated_sizes(&self, roots: &[NodeIndex]) -> Vec<usize> {
+        let node_count = self.graph.node_count();
+
+        // same graph without the items, plus the virtual root
+        let mut graph: Graph<(), ()> = Graph::with_capacity(node_count + 1, 0);
+        for _ in 0..node_count {
+            graph.add_node(());
+        }
+        for edge in self.graph.raw_edges() {
+            graph.add_edge(edge.source(), edge.target(), ());
+        }
+
+        let virtual_root = graph.add_node(());
+        for &root in roots {
+            graph.add_edge(virtual_root, root, ());
+        }
+
+        let dominators = simple_fast(&graph, virtual_root);
+
+        let mut sizes = vec![0; node_count];
+        for node in self.graph.node_indices() {
+            // every strict dominator of a node is on its chain of immediate dominators
+            let mut hop = node;
+            while let Some(dominator) = dominators.immediate_dominator(hop) {
+                if dominator == virtual_root {
+                    break;
+                }
+
+                sizes[dominator.index()] += 1;
+                hop = dominator;
+            }
+        }
+
+        sizes
+    }
+
+    /// Longest call chain below each node, computed on the condensation DAG so that calls
+    /// inside a recursive cluster don't count. Leaves have a height of 0.
+    pub fn heights(&self) -> Vec<usize> {
+        let sccs = &self.sccs;
+        let component = &self.component;
+
+        // sccs are in reverse topological order, so all callees of a component
+        // are done before it
+        let mut component_heights = vec![0; sccs.len()];
+        for (i, scc) in sccs.iter().enumerate() {
+            component_heights[i] = scc
+                .iter()
+                .flat_map(|&node| self.graph.neighbors(node))
+                .map(|neighbor| component[neighbor.index()])
+                .filter(|&j| j != i)
+                .map(|j| component_heights[j] + 1)
+                .max()
+                .unwrap_or(0);
+        }
+
+        self.graph
+            .node_indices()
+            .map(|node| component_heights[component[node.index()]])
+            .collect()
+    }
+
+    /// Simple paths from `from` to `to`, shortest first, up to `max_paths` of them. Uses Yen's
+    /// algorithm, so each path costs a bounded number of bfs runs, and there's no search at all
+    /// if `to` can't be reached.
+    pub fn paths(&self, from: NodeIndex, to: NodeIndex, max_paths: usize) -> Vec<Vec<NodeIndex>> {
+        if max_paths == 0 {
+            return vec![];
+        }
+
+        let Some(shortest) = self.shortest_path(from, to, &HashSet::new(), &HashSet::new()) else {
+            return vec![];
+        };
+
+        let mut paths = vec![shortest];
+        let mut candidates: Vec<Vec<NodeIndex>> = vec![];
+
+        while paths.len() < max_paths {
+            let last = paths.last().unwrap();
+
+            // deviate from the last path at each of its nodes
+            for i in 0..last.len() - 1 {
+                let prefix = &last[..=i];
+
+                // calls already taken after the same prefix
+                let removed_edges = paths
+                    .iter()
+                    .filter(|path| path.len() > i + 1 && path[..=i] == *prefix)
+                    .map(|path| (path[i], path[i + 1]))
+                    .collect::<HashSet<_>>();
+                let removed_nodes = last[..i].iter().copied().collect::<HashSet<_>>();
+
+                let Some(suffix) = self.shortest_path(last[i], to, &removed_nodes, &removed_edges)
+                else {
+                    continue;
+                };
+
+                let candidate = last[..i].iter().copied().chain(suffix).collect::<Vec<_>>();
+                if !candidates.contains(&candidate) && !paths.contains(&candidate) {
+                    candidates.push(candidate);
+                }
+            }
+
+            let Some(next) = (0..candidates.len()).min_by_key(|&j| candidates[j].len()) else {
+                break;
+            };
+
+            paths.push(candidates.remove(next));
+        }
+
+        paths
+    }
+
+    /// Shortest path from `from` to `to` (including both) avoiding the given nodes and calls
+    fn shortest_path(
+        &self,
+        from: NodeIndex,
+        to: NodeIndex,
+        removed_nodes: &HashSet<NodeIndex>,
+        removed_edges: &HashSet<(NodeIndex, NodeIndex)>,
+    ) -> Option<Vec<NodeIndex>> {
+        let mut predecessors = HashMap::new();
+        let mut visited = HashSet::from([from]);
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(node) = queue.pop_front() {
+            if node == to {
+                let mut path = vec![to];
+                while let Some(&predecessor) = predecessors.get(path.last().unwrap()) {
+                    path.push(predecessor);
+                }
+
+                path.reverse();
+
+                return Some(path);
+            }
+
+            for neighbor in self.graph.neighbors(node) {
+                if removed_nodes.contains(&neighbor)
+                    || removed_edges.contains(&(node, neighbor))
+                    || !visited.insert(neighbor)
+                {
+                    continue;
+                }
+
+                predecessors.insert(neighbor, node);
+                queue.push_back(neighbor);
+            }
+        }
+
+        None
+    }
+
+    /// Tree of the callers or callees of `node`, up to `max_depth` levels. Recursion is
+    /// cut at the first repeated node in each branch.
+    pub fn call_tree(
+        &self,
+        node: NodeIndex,
+        max_depth: usize,
+        direction: Direction,
+    ) -> CallTree<T> {
+        let mut branch = vec![node];
+
+        self.build_call_tree(&mut branch, max_depth, direction)
+    }
+
+    fn build_call_tree(
+        &self,
+        branch: &mut Vec<NodeIndex>,
+        max_depth: usize,
+        direction: Direction,
+    ) -> CallTree<T> {
+        let node = *branch.last().unwrap();
+
+        let mut neighbors = self
+            .graph
+            .neighbors_directed(node, direction)
+            .collect::<Vec<_>>();
+        neighbors.sort();
+        neighbors.dedup();
+
+        let mut children = vec![];
+        if branch.len() <= max_depth {
+            for neighbor in neighbors {
+                if branch.contains(&neighbor) {
+                    continue;
+                }
+
+                branch.push(neighbor);
+                children.push(self.build_call_tree(branch, max_depth, direction));
+                branch.pop();
+            }
+        }
+
+        CallTree {
+            item: self.item(node).clone(),
+            children,
+        }
+    }
+
+    /// Components with more than one node, or a single node calling itself
+    pub fn recursive_clusters(&self) -> Vec<RecursiveCluster<T>> {
+        // calls into each component from outside of it
+        let mut entries = vec![vec![]; self.sccs.len()];
+        for edge in self.graph.raw_edges() {
+            let (s, t) = (self.component(edge.source()), self.component(edge.target()));
+            if s != t {
+                entries[t].push((edge.source(), edge.target()));
+            }
+        }
+
+        self.sccs
+            .iter()
+            .zip(entries)
+            .filter(|(scc, _)| scc.len() > 1 || self.graph.contains_edge(scc[0], scc[0]))
+            .map(|(scc, entries)| RecursiveCluster {
+                members: scc.iter().map(|&n| self.item(n).clone()).collect(),
+                entries: entries
+                    .into_iter()
+                    .map(|(s, t)| (self.item(s).clone(), self.item(t).clone()))
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn item(&self, node: NodeIndex) -> &T {
+        &self.graph[node]
+    }
+}
+
+/// Transitive callers or callees of an item
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallTree<T> {
+    pub item: T,
+    pub children: Vec<CallTree<T>>,
+}
+
+/// A call to remove (or invert) to make the call graph acyclic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedbackArc<T> {
+    pub caller: T,
+    pub callee: T,
+    /// Number of simple cycles going through this call
+    pub cycles: usize,
+    /// Whether counting stopped at a limit, so there are at least `cycles` cycles
+    pub truncated: bool,
+}
+
+/// Call graph metrics of a single function
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FunctionMetrics<T> {
+    #[serde(skip)]
+    pub item: T,
+    /// Number of distinct direct callers
+    pub fan_in: usize,
+    /// Number of distinct direct callees
+    pub fan_out: usize,
+    pub transitive_callers: usize,
+    pub transitive_callees: usize,
+    /// Number of shortest call chains between other functions passing through this function,
+    /// split evenly between chains of the same length
+    pub betweenness: f64,
+}
+
+/// Functions that (indirectly) call each other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecursiveCluster<T> {
+    pub members: Vec<T>,
+    /// Calls into the cluster from outside of it
+    pub entries: Vec<(T, T)>,
+}
+
+/// Shortest paths from each root to each node reachable from it, stored as predecessor
+/// pointers so that paths are only built when needed
+pub struct RootPaths {
+    roots: Vec<NodeIndex>,
+    /// nodes reachable from each root (by position in `roots`), in order of depth
+    reached: Vec<Vec<NodeIndex>>,
+    depths: HashMap<(usize, NodeIndex), usize>,
+    predecessors: HashMap<(usize, NodeIndex), NodeIndex>,
+}
+
+impl RootPaths {
+    pub fn roots(&self) -> &[NodeIndex] {
+        &self.roots
+    }
 
-type ItemPathFromRoot<T> = (T, Vec<T>);
-pub fn get_depths<T>(edges: &Vec<(T, T)>) -> Vec<(T, Vec<ItemPathFromRoot<T>>)>
+    /// Nodes reachable from the root at position `root`, in order of depth
+    pub fn reached(&self, root: usize) -> &[NodeIndex] {
+        &self.reached[root]
+    }
+
+    /// Depth of `node` from the root at position `root`
+    pub fn depth(&self, root: usize, node: NodeIndex) -> usize {
+        self.depths[&(root, node)]
+    }
+
+    /// Shortest path from the root at position `root` to `node`, including both
+    pub fn path(&self, root: usize, node: NodeIndex) -> Vec<NodeIndex> {
+        let mut path = vec![node];
+
+        let mut hop = node;
+        while let Some(&predecessor) = self.predecessors.get(&(root, hop)) {
+            path.push(predecessor);
+            hop = predecessor;
+        }
+
+        path.reverse();
+
+        path
+    }
+}
+
+/// Run a single search from all roots at once, keeping track of each root separately. Depths
+/// are computed on the condensation DAG: calls inside a recursive cluster don't add to the
+/// depth, so all of its members share the same depth, whichever of them is reached first.
+pub fn bfs_from_roots<T>(graph: &CallGraph<T>, roots: &[NodeIndex]) -> RootPaths {
+    let mut reached = vec![vec![]; roots.len()];
+    let mut depths = HashMap::new();
+    let mut predecessors = HashMap::new();
+    let mut done = HashSet::new();
+    let mut queue = VecDeque::new();
+
+    for (root, &node) in roots.iter().enumerate() {
+        depths.insert((root, node), 0);
+        queue.push_back((root, node));
+    }
+
+    // 0-1 bfs: calls inside a component are pushed to the front, so nodes are done in order
+    // of depth
+    while let Some((root, node)) = queue.pop_front() {
+        if !done.insert((root, node)) {
+            continue;
+        }
+
+        reached[root].push(node);
+        let depth = depths[&(root, node)];
+
+        for neighbor in graph.graph.neighbors(node) {
+            let inside = graph.component[neighbor.index()] == graph.component[node.index()];
+            let neighbor_depth = if inside { depth } else { depth + 1 };
+
+            if depths
+                .get(&(root, neighbor))
+                .is_some_and(|&d| d <= neighbor_depth)
+            {
+                continue;
+            }
+
+            depths.insert((root, neighbor), neighbor_depth);
+            predecessors.insert((root, neighbor), node);
+
+            if inside {
+                queue.push_front((root, neighbor));
+            } else {
+                queue.push_back((root, neighbor));
+            }
+        }
+    }
+
+    RootPaths {
+        roots: roots.to_vec(),
+        reached,
+        depths,
+        predecessors,
+    }
+}
+
+/// A call chain from a root to an item. Calls inside a recursive cluster don't add to the
+/// depth, so it can be smaller than the number of calls in `path`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CallPath<T> {
+    pub depth: usize,
+    /// Items from the root to the item, including both
+    pub path: Vec<T>,
+}
+
+type ItemPathFromRoot<T> = (T, CallPath<T>);
+/// Path to each reachable item, from each root
+pub type DepthsByRoot<T> = Vec<(T, Vec<ItemPathFromRoot<T>>)>;
+
+pub fn get_depths<T>(graph: &CallGraph<T>) -> DepthsByRoot<T>
 where
     T: Clone + Hash + Eq + Debug,
 {
-    // find all roots and execute a bfs from each one to get depths
+    // find all roots and execute a bfs from them to get depths
     // of each node from each root
-    let targets = edges.iter().map(|e| e.1.clone()).collect::<HashSet<_>>();
-    let mut roots = HashSet::new();
-    for (s, _) in edges {
-        if !targets.contains(s) {
-            roots.insert(s);
-        }
+    let roots = graph.roots();
+
+    collect_depths(graph, &roots)
+}
+
+/// Like [`get_depths`], but starting only from the given entries. Entries that
+/// don't appear in any edge are skipped.
+pub fn get_depths_from_entries<T>(graph: &CallGraph<T>, entries: &[T]) -> DepthsByRoot<T>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let mut roots = entries
+        .iter()
+        .filter_map(|entry| graph.node(entry))
+        .collect::<Vec<_>>();
+
+    roots.sort();
+    roots.dedup();
+
+    collect_depths(graph, &roots)
+}
+
+/// Longest path (witness) from any root to each item, see [`CallGraph::longest_paths`].
+/// Uses the given entries as roots if there are any.
+pub fn get_max_depths<T>(graph: &CallGraph<T>, entries: Option<&[T]>) -> Vec<ItemPathFromRoot<T>>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let roots = graph.entry_roots(entries);
+
+    graph
+        .longest_paths(&roots)
+        .into_iter()
+        .map(|(node, path)| {
+            (
+                graph.item(node).clone(),
+                CallPath {
+                    depth: path.depth,
+                    path: path
+                        .path
+                        .into_iter()
+                        .map(|hop| graph.item(hop).clone())
+                        .collect(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Items reachable from any of the entries, including the entries in the graph
+pub fn get_reachable<T>(graph: &CallGraph<T>, entries: &[T]) -> HashSet<T>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let roots = graph.entry_roots(Some(entries));
+
+    graph
+        .reachable(&roots)
+        .into_iter()
+        .map(|node| graph.item(node).clone())
+        .collect()
+}
+
+/// Number of items dominated by each item, see [`CallGraph::dominated_sizes`].
+/// Uses the given entries as roots if there are any.
+pub fn get_dominated_sizes<T>(graph: &CallGraph<T>, entries: Option<&[T]>) -> Vec<(T, usize)>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let roots = graph.entry_roots(entries);
+
+    graph
+        .dominated_sizes(&roots)
+        .into_iter()
+        .enumerate()
+        .map(|(node, size)| (graph.item(NodeIndex::new(node)).clone(), size))
+        .collect()
+}
+
+/// Get up to `max_paths` call paths from `from` to `to`, shortest first
+pub fn get_paths<T>(graph: &CallGraph<T>, from: &T, to: &T, max_paths: usize) -> Vec<Vec<T>>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let (Some(from), Some(to)) = (graph.node(from), graph.node(to)) else {
+        return vec![];
+    };
+
+    graph
+        .paths(from, to, max_paths)
+        .into_iter()
+        .map(|path| {
+            path.into_iter()
+                .map(|hop| graph.item(hop).clone())
+                .collect()
+        })
+        .collect()
+}
+
+/// Get the tree of transitive callers (`Direction::Incoming`) or callees
+/// (`Direction::Outgoing`) of an item, up to `max_depth` levels
+pub fn get_call_tree<T>(
+    graph: &CallGraph<T>,
+    item: &T,
+    max_depth: usize,
+    direction: Direction,
+) -> CallTree<T>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    match graph.node(item) {
+        Some(node) => graph.call_tree(node, max_depth, direction),
+        // items without any calls have no callers or callees
+        None => CallTree {
+            item: item.clone(),
+            children: vec![],
+        },
     }
+}
 
-    roots
+/// Get all items in topological order (callers before callees). Items in the same recursive
+/// cluster are kept together, in order of first appearance.
+pub fn get_topological_order<T>(graph: &CallGraph<T>) -> Vec<T>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    // sccs are in reverse topological order
+    graph
+        .sccs()
         .iter()
-        .map(|&r| (r.clone(), get_root_paths(r, edges)))
+        .rev()
+        .flatten()
+        .map(|&node| graph.item(node).clone())
         .collect()
 }
 
-fn get_root_paths<T>(root: &T, edges: &Vec<(T, T)>) -> Vec<(T, Vec<T>)>
+/// Suggest calls to remove to make the call graph acyclic, using the greedy heuristic of
+/// Eades et al. for a minimum feedback arc set. Cycles through each call are counted inside
+/// its recursive cluster, up to `max_cycles` of them or `max_steps` calls followed, since there
+/// can be exponentially many of them.
+pub fn get_feedback_arcs<T>(
+    graph: &CallGraph<T>,
+    max_cycles: usize,
+    max_steps: usize,
+) -> Vec<FeedbackArc<T>>
 where
     T: Clone + Hash + Eq + Debug,
 {
-    // build graph
-    let mut to_graph_node = HashMap::new();
-    let mut graph: Graph<T, ()> = Graph::new();
+    let mut arcs = greedy_feedback_arc_set(&graph.graph)
+        .map(|edge| (edge.source(), edge.target()))
+        .collect::<Vec<_>>();
+
+    // the same call may appear more than once
+    arcs.sort();
+    arcs.dedup();
+
+    arcs.into_iter()
+        .map(|(caller, callee)| {
+            // each cycle through caller -> callee is a simple path back from callee to caller
+            let (cycles, truncated) = if caller == callee {
+                (1, false)
+            } else {
+                count_cluster_paths(graph, callee, caller, max_cycles, max_steps)
+            };
+
+            FeedbackArc {
+                caller: graph.item(caller).clone(),
+                callee: graph.item(callee).clone(),
+                cycles,
+                truncated,
+            }
+        })
+        .collect()
+}
+
+/// Count the simple paths from `from` to `to` that stay inside their recursive cluster, with a
+/// dfs stopping after `max_paths` paths or `max_steps` calls followed. Also returns whether the
+/// count was cut off.
+fn count_cluster_paths<T>(
+    graph: &CallGraph<T>,
+    from: NodeIndex,
+    to: NodeIndex,
+    max_paths: usize,
+    max_steps: usize,
+) -> (usize, bool)
+where
+    T: Clone + Hash + Eq,
+{
+    let component = graph.component(from);
+    if graph.component(to) != component {
+        return (0, false);
+    }
 
-    for (s, t) in edges {
-        if !to_graph_node.contains_key(s) {
-            to_graph_node.insert(s, graph.add_node(s.clone()));
+    let callees = |node: NodeIndex| {
+        graph
+            .callees(node)
+            .into_iter()
+            .filter(|&callee| graph.component(callee) == component)
+            .collect::<Vec<_>>()
+    };
+
+    let mut on_path = HashSet::from([from]);
+    let mut stack = vec![(from, callees(from), 0)];
+
+    let mut paths = 0;
+    let mut steps = 0;
+    while let Some((node, neighbors, next)) = stack.last_mut() {
+        let Some(&neighbor) = neighbors.get(*next) else {
+            on_path.remove(node);
+            stack.pop();
+            continue;
+        };
+        *next += 1;
+
+        steps += 1;
+        if steps > max_steps {
+            return (paths, true);
         }
 
-        if !to_graph_node.contains_key(t) {
-            to_graph_node.insert(t, graph.add_node(t.clone()));
+        if neighbor == to {
+            paths += 1;
+            if paths == max_paths {
+                return (paths, true);
+            }
+        } else if on_path.insert(neighbor) {
+            stack.push((neighbor, callees(neighbor), 0));
         }
+    }
 
-        graph.add_edge(
-            *to_graph_node.get(s).unwrap(),
-            *to_graph_node.get(t).unwrap(),
-            (),
-        );
+    (paths, false)
+}
+
+/// Get the height (longest call chain below it) of each item, see [`CallGraph::heights`]
+pub fn get_heights<T>(graph: &CallGraph<T>) -> HashMap<T, usize>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    graph
+        .heights()
+        .into_iter()
+        .enumerate()
+        .map(|(node, height)| (graph.item(NodeIndex::new(node)).clone(), height))
+        .collect()
+}
+
+/// Get the metrics of each item in the call graph, in order of first appearance
+pub fn get_metrics<T>(graph: &CallGraph<T>) -> Vec<FunctionMetrics<T>>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let node_count = graph.graph.node_count();
+
+    // the same call may appear more than once, so dedup neighbors
+    let mut callees = vec![vec![]; node_count];
+    let mut callers = vec![vec![]; node_count];
+    for node in graph.nodes() {
+        let neighbors = graph
+            .callees(node)
+            .into_iter()
+            .map(|n| n.index())
+            .collect::<Vec<_>>();
+
+        for &neighbor in &neighbors {
+            callers[neighbor].push(node.index());
+        }
+
+        callees[node.index()] = neighbors;
     }
 
-    // run bfs
-    let mut graph_depths = vec![];
+    let betweenness = get_betweenness(&callees);
 
-    let mut paths = vec![vec![*to_graph_node.get(root).unwrap()]];
-    let mut visited = HashSet::new();
-    while !paths.is_empty() {
-        let mut new_paths = vec![];
-        for path in paths {
-            let path_head = *path.last().unwrap();
-            if !visited.contains(&path_head) {
-                graph_depths.push((path_head, path.clone()));
-                visited.insert(path_head);
+    (0..node_count)
+        .map(|node| FunctionMetrics {
+            item: graph.item(NodeIndex::new(node)).clone(),
+            fan_in: callers[node].len(),
+            fan_out: callees[node].len(),
+            transitive_callers: count_reachable(&callers, node),
+            transitive_callees: count_reachable(&callees, node),
+            betweenness: betweenness[node],
+        })
+        .collect()
+}
 
-                for neighbor in graph.neighbors(path_head) {
-                    let mut new_path = path.clone();
-                    new_path.push(neighbor);
-                    new_paths.push(new_path);
+/// Count the nodes reachable from `start`, not including itself
+fn count_reachable(adjacency: &[Vec<usize>], start: usize) -> usize {
+    let mut visited = vec![false; adjacency.len()];
+    let mut stack = vec![start];
+
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        for &neighbor in &adjacency[node] {
+            if !visited[neighbor] {
+                visited[neighbor] = true;
+                stack.push(neighbor);
+
+                if neighbor != start {
+                    count += 1;
                 }
             }
         }
+    }
 
-        paths = new_paths;
+    count
+}
+
+/// Brandes' algorithm for betweenness centrality in an unweighted directed graph
+fn get_betweenness(adjacency: &[Vec<usize>]) -> Vec<f64> {
+    let node_count = adjacency.len();
+    let mut betweenness = vec![0.0; node_count];
+
+    for source in 0..node_count {
+        let mut order = vec![];
+        let mut predecessors = vec![vec![]; node_count];
+        let mut paths = vec![0.0; node_count];
+        let mut distances: Vec<Option<usize>> = vec![None; node_count];
+
+        paths[source] = 1.0;
+        distances[source] = Some(0);
+
+        let mut queue = VecDeque::from([source]);
+        while let Some(node) = queue.pop_front() {
+            order.push(node);
+            let distance = distances[node].unwrap();
+
+            for &neighbor in &adjacency[node] {
+                if distances[neighbor].is_none() {
+                    distances[neighbor] = Some(distance + 1);
+                    queue.push_back(neighbor);
+                }
+
+                if distances[neighbor] == Some(distance + 1) {
+                    paths[neighbor] += paths[node];
+                    predecessors[neighbor].push(node);
+                }
+            }
+        }
+
+        // accumulate dependencies from the farthest nodes back to the source
+        let mut dependencies = vec![0.0; node_count];
+        for &node in order.iter().rev() {
+            for &predecessor in &predecessors[node] {
+                dependencies[predecessor] +=
+                    paths[predecessor] / paths[node] * (1.0 + dependencies[node]);
+            }
+
+            if node != source {
+                betweenness[node] += dependencies[node];
+            }
+        }
     }
 
+    betweenness
+}
+
+fn collect_depths<T>(graph: &CallGraph<T>, roots: &[NodeIndex]) -> DepthsByRoot<T>
+where
+    T: Clone + Hash + Eq + Debug,
+{
+    let root_paths = bfs_from_roots(graph, roots);
+
     // convert graph nodes to real nodes
-    graph_depths
+    root_paths
+        .roots()
         .iter()
-        .map(|(n, d)| {
-            (
-                graph.node_weight(*n).unwrap().clone(),
-                d.iter()
-                    .map(|p| graph.node_weight(*p).unwrap().clone())
-                    .collect::<Vec<_>>(),
-            )
+        .enumerate()
+        .map(|(root, &root_node)| {
+            let item_paths = root_paths
+                .reached(root)
+                .iter()
+                .map(|&node| {
+                    (
+                        graph.item(node).clone(),
+                        CallPath {
+                            depth: root_paths.depth(root, node),
+                            path: root_paths
+                                .path(root, node)
+                                .into_iter()
+                                .map(|hop| graph.item(hop).clone())
+                                .collect(),
+                        },
+                    )
+                })
+                .collect();
+
+            (graph.item(root_node).clone(), item_paths)
         })
-        .collect::<Vec<_>>()
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::get_depths;
+    use super::{
+        count_cluster_paths, get_call_tree, get_depths, get_depths_from_entries,
+        get_dominated_sizes, get_feedback_arcs, get_heights, get_max_depths, get_metrics,
+        get_paths, get_reachable, get_topological_order, CallGraph, CallPath, CallTree, Direction,
+        FeedbackArc, RecursiveCluster,
+    };
+
+    /// Path without recursive clusters, where each call adds to the depth
+    fn chain(path: &[i32]) -> CallPath<i32> {
+        CallPath {
+            depth: path.len() - 1,
+            path: path.to_vec(),
+        }
+    }
 
     #[test]
     fn test_get_depths() {
         assert_eq!(
-            get_depths(&(vec![(0, 1), (1, 2), (2, 3)])),
+            get_depths(&CallGraph::new(&[(0, 1), (1, 2), (2, 3)])),
             vec![(
                 0,
                 vec![
-                    (0, vec![0]),
-                    (1, vec![0, 1]),
-                    (2, vec![0, 1, 2]),
-                    (3, vec![0, 1, 2, 3]),
+                    (0, chain(&[0])),
+                    (1, chain(&[0, 1])),
+                    (2, chain(&[0, 1, 2])),
+                    (3, chain(&[0, 1, 2, 3])),
                 ]
             )]
         );
@@ -111,45 +1037,372 @@ mod tests {
 
     #[test]
     fn test_get_depths_2_roots() {
-        let depths = get_depths(&vec![
+        let depths = get_depths(&CallGraph::new(&[
             (0, 1), // root 1
             (1, 2),
             (2, 3),
             (10, 11), // root 2
             (11, 12),
             (12, 13),
-        ]);
+        ]));
 
         assert!(depths.contains(&(
             0,
             vec![
-                (0, vec![0]),
-                (1, vec![0, 1]),
-                (2, vec![0, 1, 2]),
-                (3, vec![0, 1, 2, 3]),
+                (0, chain(&[0])),
+                (1, chain(&[0, 1])),
+                (2, chain(&[0, 1, 2])),
+                (3, chain(&[0, 1, 2, 3])),
             ]
         )));
         assert!(depths.contains(&(
             10,
             vec![
-                (10, vec![10]),
-                (11, vec![10, 11]),
-                (12, vec![10, 11, 12]),
-                (13, vec![10, 11, 12, 13]),
+                (10, chain(&[10])),
+                (11, chain(&[10, 11])),
+                (12, chain(&[10, 11, 12])),
+                (13, chain(&[10, 11, 12, 13])),
             ]
         )));
     }
 
+    #[test]
+    fn test_get_depths_shared_node() {
+        assert_eq!(
+            get_depths(&CallGraph::new(&[(0, 1), (1, 2), (10, 2)])),
+            vec![
+                (
+                    0,
+                    vec![
+                        (0, chain(&[0])),
+                        (1, chain(&[0, 1])),
+                        (2, chain(&[0, 1, 2]))
+                    ]
+                ),
+                (10, vec![(10, chain(&[10])), (2, chain(&[10, 2]))]),
+            ]
+        );
+    }
+
     #[test]
     fn test_get_depths_loop() {
         assert_eq!(
-            get_depths(&(vec![(0, 1), (0, 2), (1, 2), (2, 1)])),
-            vec![(0, vec![(0, vec![0]), (2, vec![0, 2]), (1, vec![0, 1]),])]
+            get_depths(&CallGraph::new(&[(0, 1), (0, 2), (1, 2), (2, 1)])),
+            vec![(
+                0,
+                vec![(0, chain(&[0])), (2, chain(&[0, 2])), (1, chain(&[0, 1]))]
+            )]
+        );
+
+        // all members of a cluster get the depth it's entered at
+        assert_eq!(
+            get_depths(&CallGraph::new(&[(0, 1), (1, 2), (2, 1), (2, 3)])),
+            vec![(
+                0,
+                vec![
+                    (0, chain(&[0])),
+                    (1, chain(&[0, 1])),
+                    (
+                        2,
+                        CallPath {
+                            depth: 1,
+                            path: vec![0, 1, 2]
+                        }
+                    ),
+                    (
+                        3,
+                        CallPath {
+                            depth: 2,
+                            path: vec![0, 1, 2, 3]
+                        }
+                    ),
+                ]
+            )]
         );
     }
 
     #[test]
     fn test_top_level_loop() {
-        assert_eq!(get_depths(&(vec![(0, 1), (1, 0)])), vec![]);
+        // whichever member is picked as the root, the whole cluster is at depth 0
+        assert_eq!(
+            get_depths(&CallGraph::new(&[(0, 1), (1, 0)])),
+            vec![(
+                0,
+                vec![
+                    (0, chain(&[0])),
+                    (
+                        1,
+                        CallPath {
+                            depth: 0,
+                            path: vec![0, 1]
+                        }
+                    )
+                ]
+            )]
+        );
+    }
+
+    #[test]
+    fn test_get_depths_from_entries() {
+        assert_eq!(
+            get_depths_from_entries(&CallGraph::new(&[(0, 1), (1, 2), (3, 1), (4, 5)]), &[1, 6]),
+            vec![(1, vec![(1, chain(&[1])), (2, chain(&[1, 2]))])]
+        );
+    }
+
+    #[test]
+    fn test_get_max_depths() {
+        let in_cluster = |depth, path: &[i32]| CallPath {
+            depth,
+            path: path.to_vec(),
+        };
+
+        assert_eq!(
+            get_max_depths(
+                &CallGraph::new(&[(0, 1), (1, 2), (0, 2), (2, 3), (3, 2), (3, 4)]),
+                None
+            ),
+            vec![
+                (0, chain(&[0])),
+                (1, chain(&[0, 1])),
+                (2, chain(&[0, 1, 2])),
+                (3, in_cluster(2, &[0, 1, 2, 3])),
+                (4, in_cluster(3, &[0, 1, 2, 3, 4])),
+            ]
+        );
+
+        // paths enter the cluster through the call that gives it its depth
+        assert_eq!(
+            get_max_depths(
+                &CallGraph::new(&[(0, 1), (1, 3), (3, 2), (2, 3), (2, 4), (0, 2)]),
+                None
+            ),
+            vec![
+                (0, chain(&[0])),
+                (1, chain(&[0, 1])),
+                (3, chain(&[0, 1, 3])),
+                (2, in_cluster(2, &[0, 1, 3, 2])),
+                (4, in_cluster(3, &[0, 1, 3, 2, 4])),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_max_depths_from_entries() {
+        assert_eq!(
+            get_max_depths(&CallGraph::new(&[(0, 1), (1, 2), (3, 2)]), Some(&[3])),
+            vec![(2, chain(&[3, 2])), (3, chain(&[3]))]
+        );
+    }
+
+    #[test]
+    fn test_get_reachable() {
+        assert_eq!(
+            get_reachable(&CallGraph::new(&[(0, 1), (1, 2), (2, 1), (3, 4)]), &[1, 5]),
+            [1, 2].into_iter().collect()
+        );
+    }
+
+    #[test]
+    fn test_get_dominated_sizes() {
+        // 3 can be reached from root 5 bypassing 0 and 1, so only 3 is a chokepoint for 4
+        assert_eq!(
+            get_dominated_sizes(
+                &CallGraph::new(&[(0, 1), (1, 2), (1, 3), (2, 3), (3, 4), (5, 3)]),
+                None
+            ),
+            vec![(0, 2), (1, 1), (2, 0), (3, 1), (4, 0), (5, 0)]
+        );
+
+        assert_eq!(
+            get_dominated_sizes(
+                &CallGraph::new(&[(0, 1), (1, 2), (1, 3), (2, 3), (3, 4), (5, 3)]),
+                Some(&[0])
+            ),
+            vec![(0, 4), (1, 3), (2, 0), (3, 1), (4, 0), (5, 0)]
+        );
+    }
+
+    #[test]
+    fn test_get_metrics() {
+        let metrics = get_metrics(&CallGraph::new(&[
+            (0, 1),
+            (0, 2),
+            (1, 3),
+            (2, 3),
+            (3, 4),
+            (3, 4),
+        ]));
+
+        let summary = metrics
+            .iter()
+            .map(|m| {
+                (
+                    m.item,
+                    m.fan_in,
+                    m.fan_out,
+                    m.transitive_callers,
+                    m.transitive_callees,
+                    m.betweenness,
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            vec![
+                (0, 0, 2, 0, 4, 0.0),
+                (1, 1, 1, 1, 2, 1.0),
+                (2, 1, 1, 1, 2, 1.0),
+                (3, 2, 1, 3, 1, 3.0),
+                (4, 1, 0, 4, 0, 0.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_get_paths() {
+        let edges = [(0, 1), (1, 2), (2, 3), (0, 3), (0, 2), (3, 0)];
+
+        assert_eq!(
+            get_paths(&CallGraph::new(&edges), &0, &3, 1),
+            vec![vec![0, 3]]
+        );
+        assert_eq!(
+            get_paths(&CallGraph::new(&edges), &0, &3, 10),
+            vec![vec![0, 3], vec![0, 2, 3], vec![0, 1, 2, 3]]
+        );
+        assert_eq!(
+            get_paths(&CallGraph::new(&edges), &3, &4, 10),
+            Vec::<Vec<i32>>::new()
+        );
+
+        // both items are in the graph, but there's no path between them
+        let edges = [(0, 1), (1, 2), (2, 1), (3, 4)];
+        assert_eq!(
+            get_paths(&CallGraph::new(&edges), &0, &4, 10),
+            Vec::<Vec<i32>>::new()
+        );
+        assert_eq!(
+            get_paths(&CallGraph::new(&edges), &0, &2, 10),
+            vec![vec![0, 1, 2]]
+        );
+    }
+
+    #[test]
+    fn test_get_call_tree() {
+        let edges = [(0, 1), (1, 2), (2, 0), (1, 3)];
+        let leaf = |item| CallTree {
+            item,
+            children: vec![],
+        };
+
+        assert_eq!(
+            get_call_tree(&CallGraph::new(&edges), &0, 5, Direction::Outgoing),
+            CallTree {
+                item: 0,
+                children: vec![CallTree {
+                    item: 1,
+                    children: vec![leaf(2), leaf(3)],
+                }],
+            }
+        );
+        assert_eq!(
+            get_call_tree(&CallGraph::new(&edges), &2, 1, Direction::Incoming),
+            CallTree {
+                item: 2,
+                children: vec![leaf(1)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_topological_order() {
+        assert_eq!(
+            get_topological_order(&CallGraph::new(&[
+                (3, 1),
+                (0, 1),
+                (1, 2),
+                (2, 1),
+                (2, 4),
+                (0, 3)
+            ])),
+            vec![0, 3, 1, 2, 4]
+        );
+    }
+
+    #[test]
+    fn test_get_feedback_arcs() {
+        let edges = [(0, 1), (1, 2), (2, 0), (2, 3), (3, 1), (4, 4), (5, 0)];
+        let arcs = get_feedback_arcs(&CallGraph::new(&edges), 10, 1000);
+
+        // every cycle must be broken
+        let remaining = edges
+            .iter()
+            .filter(|&&(s, t)| !arcs.iter().any(|arc| (arc.caller, arc.callee) == (s, t)))
+            .cloned()
+            .collect::<Vec<_>>();
+        assert!(CallGraph::new(&remaining).recursive_clusters().is_empty());
+
+        assert!(arcs.len() <= 3);
+        assert!(arcs.contains(&FeedbackArc {
+            caller: 4,
+            callee: 4,
+            cycles: 1,
+            truncated: false,
+        }));
+    }
+
+    #[test]
+    fn test_count_cluster_paths() {
+        // 0 -> 1 -> 3 and 0 -> 2 -> 3 lead back to 0, 4 is outside of the cluster
+        let graph = CallGraph::new(&[(0, 1), (0, 2), (1, 3), (2, 3), (3, 0), (3, 4), (4, 4)]);
+        let node = |item| graph.node(&item).unwrap();
+
+        assert_eq!(
+            count_cluster_paths(&graph, node(0), node(3), 10, 100),
+            (2, false)
+        );
+        assert_eq!(
+            count_cluster_paths(&graph, node(0), node(3), 1, 100),
+            (1, true)
+        );
+        assert_eq!(
+            count_cluster_paths(&graph, node(0), node(3), 10, 2),
+            (1, true)
+        );
+        assert_eq!(
+            count_cluster_paths(&graph, node(0), node(4), 10, 100),
+            (0, false)
+        );
+    }
+
+    #[test]
+    fn test_get_heights() {
+        let heights = get_heights(&CallGraph::new(&[(0, 1), (1, 2), (2, 1), (2, 3), (0, 3)]));
+
+        assert_eq!(heights[&0], 2);
+        assert_eq!(heights[&1], 1);
+        assert_eq!(heights[&2], 1);
+        assert_eq!(heights[&3], 0);
+    }
+
+    #[test]
+    fn test_recursive_clusters() {
+        let graph = CallGraph::new(&[(0, 1), (1, 2), (2, 1), (3, 2), (2, 4), (4, 4)]);
+
+        assert_eq!(
+            graph.recursive_clusters(),
+            vec![
+                RecursiveCluster {
+                    members: vec![4],
+                    entries: vec![(2, 4)],
+                },
+                RecursiveCluster {
+                    members: vec![1, 2],
+                    entries: vec![(0, 1), (3, 2)],
+                },
+            ]
+        );
     }
 }
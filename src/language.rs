@@ -0,0 +1,95 @@
+use std::path::Path;
+
+use lsp_types::SymbolKind;
+
+/// Language of the analyzed project, used to pick sensible per-language defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Rust,
+    Java,
+    Kotlin,
+    TypeScript,
+    Other,
+}
+
+impl Language {
+    /// Guess the project language from the command used to start the lsp server
+    pub fn from_lang_server_exe(lang_server_exe: &str) -> Self {
+        let exe = lang_server_exe
+            .split_ascii_whitespace()
+            .next()
+            .and_then(|exe| Path::new(exe).file_stem())
+            .and_then(|exe| exe.to_str())
+            .unwrap_or_default();
+
+        match exe {
+            "rust-analyzer" | "rust_analyzer" => Language::Rust,
+            "jdtls" => Language::Java,
+            "kotlin-language-server" => Language::Kotlin,
+            "typescript-language-server" | "tsserver" => Language::TypeScript,
+            _ => Language::Other,
+        }
+    }
+
+    /// Symbol kinds that are considered callable definitions (graph nodes)
+    pub fn callable_symbol_kinds(&self) -> Vec<SymbolKind> {
+        match self {
+            Language::Rust => vec![SymbolKind::FUNCTION, SymbolKind::METHOD],
+            // property accessors are callables in these languages
+            Language::Kotlin | Language::TypeScript => vec![
+                SymbolKind::CONSTRUCTOR,
+                SymbolKind::FUNCTION,
+                SymbolKind::METHOD,
+                SymbolKind::PROPERTY,
+            ],
+            Language::Java | Language::Other => vec![
+                SymbolKind::CONSTRUCTOR,
+                SymbolKind::FUNCTION,
+                SymbolKind::METHOD,
+            ],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use super::Language;
+
+    #[test]
+    fn test_from_lang_server_exe() {
+        assert_eq!(
+            Language::from_lang_server_exe("/usr/local/bin/rust-analyzer"),
+            Language::Rust
+        );
+        assert_eq!(
+            Language::from_lang_server_exe("jdtls -data /tmp/workspace"),
+            Language::Java
+        );
+        assert_eq!(
+            Language::from_lang_server_exe("typescript-language-server --stdio"),
+            Language::TypeScript
+        );
+        assert_eq!(Language::from_lang_server_exe("pylsp"), Language::Other);
+    }
+
+    #[test]
+    fn test_callable_symbol_kinds() {
+        let rust = Language::Rust.callable_symbol_kinds();
+        assert!(rust.contains(&SymbolKind::FUNCTION) && rust.contains(&SymbolKind::METHOD));
+        assert!(!rust.contains(&SymbolKind::CONSTRUCTOR));
+
+        assert!(Language::Java
+            .callable_symbol_kinds()
+            .contains(&SymbolKind::CONSTRUCTOR));
+
+        // property accessors are only callables in some languages
+        assert!(Language::Kotlin
+            .callable_symbol_kinds()
+            .contains(&SymbolKind::PROPERTY));
+        assert!(!Language::Java
+            .callable_symbol_kinds()
+            .contains(&SymbolKind::PROPERTY));
+    }
+}
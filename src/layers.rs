@@ -0,0 +1,196 @@
+use std::{error::Error, fs, path::Path};
+
+use lsp_types::{Location, Url};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::symbol_arena::{SymbolArena, SymbolId};
+
+/// Layers config file, with layers listed from the top (e.g. "api") to the bottom (e.g. "storage")
+#[derive(Debug, Deserialize)]
+struct LayersConfig {
+    layers: Vec<LayerConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LayerConfig {
+    name: String,
+    /// Globs over paths relative to the project root, e.g. "api/**"
+    paths: Vec<String>,
+}
+
+/// Ordered architecture layers. A layer may only call itself or the layer right below it.
+#[derive(Debug, Clone)]
+pub struct Layers {
+    layers: Vec<(String, Vec<Regex>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ViolationKind {
+    /// Call from a lower layer to a higher one
+    UpwardCall,
+    /// Call from a layer to a layer more than one level below it
+    LayerSkip,
+}
+
+#[derive(Debug, Clone)]
+pub struct LayerViolation {
+    pub kind: ViolationKind,
+    pub caller: SymbolId,
+    pub callee: SymbolId,
+    pub caller_layer: String,
+    pub callee_layer: String,
+    /// Locations of the calls inside the caller, filled in separately since they
+    /// require querying the lsp server
+    pub call_sites: Vec<Location>,
+}
+
+impl Layers {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let config: LayersConfig = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        let layers = config
+            .layers
+            .into_iter()
+            .map(|layer| {
+                let paths = layer
+                    .paths
+                    .iter()
+                    .map(|glob| glob_to_regex(glob))
+                    .collect::<Result<_, _>>()?;
+
+                Ok((layer.name, paths))
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        Ok(Self { layers })
+    }
+
+    /// Get the index of the first layer matching a path relative to the project root
+    fn layer_of(&self, relative_path: &str) -> Option<usize> {
+        self.layers
+            .iter()
+            .position(|(_, paths)| paths.iter().any(|re| re.is_match(relative_path)))
+    }
+
+    /// Check a call between two files, given by their paths relative to the project root
+    fn check(&self, caller_path: &str, callee_path: &str) -> Option<(ViolationKind, usize, usize)> {
+        let caller_layer = self.layer_of(caller_path)?;
+        let callee_layer = self.layer_of(callee_path)?;
+
+        let kind = if callee_layer < caller_layer {
+            ViolationKind::UpwardCall
+        } else if callee_layer > caller_layer + 1 {
+            ViolationKind::LayerSkip
+        } else {
+            return None;
+        };
+
+        Some((kind, caller_layer, callee_layer))
+    }
+
+    /// Check all calls between project items, ignoring items outside of any layer
+    pub fn find_violations(
+        &self,
+        arena: &SymbolArena,
+        root: &Url,
+        calls: &[(SymbolId, SymbolId)],
+    ) -> Vec<LayerViolation> {
+        let relative_path = |id: SymbolId| arena.item(id).uri.as_str().strip_prefix(root.as_str());
+
+        calls
+            .iter()
+            .filter_map(|&(caller, callee)| {
+                let (kind, caller_layer, callee_layer) =
+                    self.check(relative_path(caller)?, relative_path(callee)?)?;
+
+                Some(LayerViolation {
+                    kind,
+                    caller,
+                    callee,
+                    caller_layer: self.layers[caller_layer].0.clone(),
+                    callee_layer: self.layers[callee_layer].0.clone(),
+                    call_sites: vec![],
+                })
+            })
+            .collect()
+    }
+}
+
+/// Convert a path glob to a regex matching it at the start of any path component, so that
+/// "api/**" matches both "api/users.rs" and "src/api/users.rs"
+fn glob_to_regex(glob: &str) -> Result<Regex, regex::Error> {
+    let mut re = String::from("(^|/)");
+
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    re.push_str("(.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+
+    re.push('$');
+
+    Regex::new(&re)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_to_regex, Layers, ViolationKind};
+
+    fn layers(layers: &[(&str, &str)]) -> Layers {
+        Layers {
+            layers: layers
+                .iter()
+                .map(|(name, glob)| (name.to_string(), vec![glob_to_regex(glob).unwrap()]))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_glob_to_regex() {
+        let re = glob_to_regex("api/**").unwrap();
+        assert!(re.is_match("api/users.rs"));
+        assert!(re.is_match("src/api/v1/users.rs"));
+        assert!(!re.is_match("src/rapi/users.rs"));
+
+        let re = glob_to_regex("src/**/*.rs").unwrap();
+        assert!(re.is_match("src/main.rs"));
+        assert!(re.is_match("src/service/users.rs"));
+        assert!(!re.is_match("src/service/users.java"));
+    }
+
+    #[test]
+    fn test_check() {
+        let layers = layers(&[
+            ("api", "api/**"),
+            ("service", "service/**"),
+            ("storage", "storage/**"),
+        ]);
+
+        assert_eq!(layers.check("src/api/a.rs", "src/api/b.rs"), None);
+        assert_eq!(layers.check("src/api/a.rs", "src/service/b.rs"), None);
+        assert_eq!(layers.check("src/api/a.rs", "src/util.rs"), None);
+        assert_eq!(
+            layers.check("src/storage/a.rs", "src/service/b.rs"),
+            Some((ViolationKind::UpwardCall, 2, 1))
+        );
+        assert_eq!(
+            layers.check("src/api/a.rs", "src/storage/b.rs"),
+            Some((ViolationKind::LayerSkip, 0, 2))
+        );
+    }
+}
@@ -1,6 +1,23 @@
+pub mod abstraction;
+pub mod aggregate;
+pub mod baseline;
+pub mod cache;
+pub mod dead_code;
+pub mod diff;
+pub mod dsm;
+pub mod entry;
+pub mod explain;
+pub mod external;
 mod graph_util;
 pub mod hashable_call_hierarchy_item;
+pub mod language;
+pub mod layers;
 pub mod lsp;
+pub mod suppression;
+pub mod symbol_arena;
+#[cfg(test)]
+mod test_util;
+pub mod watch;
 
 use std::{
     collections::{HashMap, HashSet},
@@ -9,15 +26,58 @@ use std::{
     time::Duration,
 };
 
-use log::debug;
+use log::{debug, warn};
 use lsp_types::{
-    CallHierarchyItem, ClientCapabilities, DocumentSymbolClientCapabilities, InitializeParams,
-    InitializeResult, SymbolKind, TextDocumentClientCapabilities, Url,
+    CallHierarchyItem, ClientCapabilities, DocumentSymbolClientCapabilities, GotoCapability,
+    GotoDefinitionResponse, InitializeParams, InitializeResult, Location, Range, SymbolKind,
+    TextDocumentClientCapabilities, Url,
 };
 
-use graph_util::get_depths;
-use hashable_call_hierarchy_item::HashableCallHierarchyItem;
-use lsp::{json_rpc::LspError, LspClient};
+use serde::{Deserialize, Serialize};
+
+use cache::AnalysisCache;
+use graph_util::{
+    get_call_tree, get_depths, get_depths_from_entries, get_dominated_sizes, get_feedback_arcs,
+    get_max_depths, get_metrics, get_paths, get_reachable, DepthsByRoot,
+};
+use lsp::{json_rpc::LspError, type_hierarchy::TypeHierarchyItem, LspClient};
+use symbol_arena::{SymbolArena, SymbolId};
+
+pub use graph_util::{
+    CallGraph, CallPath, CallTree, FeedbackArc, FunctionMetrics, RecursiveCluster,
+};
+
+/// JSON-RPC error code for requests the server doesn't support
+const METHOD_NOT_FOUND: isize = -32601;
+
+/// JSON-RPC error code for requests about a document that changed in the meantime
+const CONTENT_MODIFIED: isize = -32801;
+
+/// How many times to retry a document symbol request the server couldn't answer yet
+const DOCUMENT_SYMBOL_RETRIES: usize = 5;
+
+/// Stop counting the cycles broken by a call after this many
+const MAX_COUNTED_CYCLES: usize = 1000;
+/// Stop counting the cycles broken by a call after following this many calls
+const MAX_CYCLE_SEARCH_STEPS: usize = 100_000;
+
+/// Options controlling how the call graph is collected from the lsp server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CallGraphOptions {
+    /// Symbol kinds that are considered callable definitions
+    pub symbol_kinds: Vec<SymbolKind>,
+    /// Include calls into dependencies and the standard library as leaf nodes
+    pub include_external: bool,
+}
+
+impl Default for CallGraphOptions {
+    fn default() -> Self {
+        Self {
+            symbol_kinds: vec![SymbolKind::FUNCTION, SymbolKind::METHOD],
+            include_external: false,
+        }
+    }
+}
 
 pub async fn init(client: &mut LspClient, root_uri: Url) -> Result<InitializeResult, LspError> {
     let params = InitializeParams {
@@ -28,6 +88,15 @@ pub async fn init(client: &mut LspClient, root_uri: Url) -> Result<InitializeRes
                     hierarchical_document_symbol_support: Some(true),
                     ..Default::default()
                 }),
+                // used by dispatch / override calls
+                declaration: Some(GotoCapability {
+                    link_support: Some(true),
+                    ..Default::default()
+                }),
+                implementation: Some(GotoCapability {
+                    link_support: Some(true),
+                    ..Default::default()
+                }),
                 ..Default::default()
             }),
             ..Default::default()
@@ -159,36 +228,56 @@ pub async fn get_function_calls(
     client: &mut LspClient,
     workspace_files: &HashSet<Url>,
     project_root: &Url,
+    options: &CallGraphOptions,
 ) -> Result<Vec<(CallHierarchyItem, CallHierarchyItem)>, Box<dyn Error>> {
-    // get exact location of each definition's name
-    let mut exact_definitions = vec![];
+    let mut cache = AnalysisCache::default();
+
+    get_function_calls_cached(client, workspace_files, project_root, options, &mut cache).await
+}
+
+/// Same as [`get_function_calls`], but only re-queries files that changed since `cache` was
+/// last updated, and updates `cache` with the results
+pub async fn get_function_calls_cached(
+    client: &mut LspClient,
+    workspace_files: &HashSet<Url>,
+    project_root: &Url,
+    options: &CallGraphOptions,
+    cache: &mut AnalysisCache,
+) -> Result<Vec<(CallHierarchyItem, CallHierarchyItem)>, Box<dyn Error>> {
+    let changed_files = cache.invalidate(workspace_files, options);
 
-    for file in workspace_files.iter() {
-        // get file symbols
-        let result = client.document_symbol(file.clone()).await.unwrap().unwrap();
+    debug!(
+        "re-querying {} of {} files",
+        changed_files.len(),
+        workspace_files.len()
+    );
 
-        match result {
-            // we need DocumentSymbol for the precise location of the function name
-            lsp_types::DocumentSymbolResponse::Flat(_) => return Err("got flat".into()),
-            lsp_types::DocumentSymbolResponse::Nested(symbols) => {
-                update_exact_definitions(symbols, file, &mut exact_definitions);
+    // get exact location of each definition's name
+    for file in workspace_files.intersection(&changed_files) {
+        // files that aren't cached are re-queried on the next run
+        let symbols = match get_document_symbols(client, file).await {
+            Ok(symbols) => symbols,
+            Err(e) => {
+                warn!("skipping {}: {}", file, e);
+                continue;
             }
-        }
+        };
+
+        cache.insert_symbols(file, symbols);
     }
 
+    let exact_definitions = cache.definitions();
+
+    // calls into unchanged files from changed files can only be found by their caller
+    let has_unchanged_files = workspace_files.iter().any(|f| !changed_files.contains(f));
+
     let mut calls = vec![];
-    for (file, definition) in exact_definitions {
+    for (file, definition) in exact_definitions
+        .iter()
+        .filter(|(file, _)| changed_files.contains(file))
+    {
         // get definition call hierarchy item
-        let target_item = lsp_types::CallHierarchyItem {
-            name: definition.name,
-            kind: definition.kind,
-            tags: definition.tags,
-            detail: definition.detail,
-            uri: file,
-            range: definition.range,
-            selection_range: definition.selection_range,
-            data: None,
-        };
+        let target_item = build_definition_item(file, definition);
 
         let result = client
             .call_hierarchy_incoming_calls(target_item.clone())
@@ -204,7 +293,17 @@ pub async fn get_function_calls(
                         .as_str()
                         .starts_with(project_root.as_str())
                     {
-                        calls.push((source_item.from, target_item.clone()));
+                        // calls from closures / lambdas are attributed to the enclosing definition
+                        let from = match find_enclosing_definition(
+                            &exact_definitions,
+                            &source_item.from.uri,
+                            &source_item.from.selection_range,
+                        ) {
+                            Some((file, definition)) => build_definition_item(file, definition),
+                            None => source_item.from,
+                        };
+
+                        calls.push((from, target_item.clone()));
                     }
                 }
             }
@@ -225,77 +324,755 @@ pub async fn get_function_calls(
                 );
             }
         }
+
+        if options.include_external || has_unchanged_files {
+            calls.append(
+                &mut get_outgoing_calls(
+                    client,
+                    &target_item,
+                    project_root,
+                    &exact_definitions,
+                    &changed_files,
+                    options.include_external,
+                )
+                .await,
+            );
+        }
+    }
+
+    cache.insert_calls(calls);
+
+    Ok(cache.calls().clone())
+}
+
+/// Get "dispatch" calls from each trait / interface method to each of its implementations,
+/// using the symbols cached by [`get_function_calls_cached`].
+///
+/// Methods implementing traits from outside the project (e.g. `impl std::fmt::Debug for A`) get
+/// a dispatch call from the trait method's declaration (found with `textDocument/declaration`).
+pub async fn get_dispatch_calls(
+    client: &mut LspClient,
+    cache: &AnalysisCache,
+    project_root: &Url,
+    options: &CallGraphOptions,
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let exact_definitions = cache.definitions();
+    let mut interface_methods = vec![];
+    let mut trait_impl_methods = vec![];
+
+    for (file, symbols) in cache.symbols() {
+        update_interface_methods(symbols, file, &options.symbol_kinds, &mut interface_methods);
+        update_trait_impl_methods(
+            symbols,
+            file,
+            &options.symbol_kinds,
+            &mut trait_impl_methods,
+        );
+    }
+
+    let mut calls = vec![];
+    for (file, method) in interface_methods.iter() {
+        let implementations = get_implementations(client, file, method, project_root).await;
+
+        calls.extend(build_implementation_calls(
+            file,
+            method,
+            &implementations,
+            &exact_definitions,
+        ));
+    }
+
+    for (file, method) in trait_impl_methods.iter() {
+        let result = client
+            .declaration(file.clone(), method.selection_range.start)
+            .await;
+
+        // traits inside the project are handled by their implementations
+        let declaration = match result {
+            Ok(Some(response)) => get_locations(response)
+                .into_iter()
+                .find(|(uri, _)| !uri.as_str().starts_with(project_root.as_str())),
+            Ok(None) => None,
+            Err(e) if e.code == METHOD_NOT_FOUND => {
+                debug!("declarations aren't supported, skipping external trait impls");
+                break;
+            }
+            Err(e) => {
+                debug!(
+                    "got jsonRpcError for declaration of {:?}: {:?} {:?}",
+                    &method.name, e.code, e.message
+                );
+                None
+            }
+        };
+
+        if let Some((uri, range)) = declaration {
+            calls.push((
+                build_declaration_item(&uri, range, method),
+                build_definition_item(file, method),
+            ));
+        }
+    }
+
+    calls
+}
+
+/// Build calls from an abstract method to the definitions enclosing each of its implementations
+fn build_implementation_calls(
+    file: &Url,
+    method: &lsp_types::DocumentSymbol,
+    implementations: &[(Url, Range)],
+    definitions: &[(Url, lsp_types::DocumentSymbol)],
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let source_item = build_definition_item(file, method);
+
+    implementations
+        .iter()
+        .filter_map(|(uri, range)| {
+            let (impl_file, implementation) = find_enclosing_definition(definitions, uri, range)?;
+
+            // some servers include the method itself, if it has a default implementation
+            if impl_file == file && implementation.range == method.range {
+                return None;
+            }
+
+            Some((
+                source_item.clone(),
+                build_definition_item(impl_file, implementation),
+            ))
+        })
+        .collect()
+}
+
+/// Get "override" calls from each class method to the methods overriding it in subclasses,
+/// using the symbols cached by [`get_function_calls_cached`]
+pub async fn get_override_calls(
+    client: &mut LspClient,
+    cache: &AnalysisCache,
+    project_root: &Url,
+    options: &CallGraphOptions,
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let mut classes = vec![];
+
+    for (file, symbols) in cache.symbols() {
+        update_classes(symbols, file, &mut classes);
+    }
+
+    // direct subtypes of each class, as indices into classes
+    let mut subtypes = vec![vec![]; classes.len()];
+    for (i, (file, class)) in classes.iter().enumerate() {
+        match get_subtypes(client, file, class).await {
+            Ok(items) => {
+                subtypes[i] = items
+                    .iter()
+                    .filter_map(|item| {
+                        let subclass =
+                            find_enclosing_definition(&classes, &item.uri, &item.selection_range)?;
+
+                        classes.iter().position(|c| std::ptr::eq(c, subclass))
+                    })
+                    .filter(|&j| j != i)
+                    .collect();
+            }
+            Err(e) if e.code == METHOD_NOT_FOUND => {
+                debug!("type hierarchy isn't supported, falling back to implementations");
+
+                return get_override_calls_from_implementations(
+                    client,
+                    &classes,
+                    project_root,
+                    options,
+                )
+                .await;
+            }
+            Err(e) => {
+                debug!(
+                    "got jsonRpcError for subtypes of {:?}: {:?} {:?}",
+                    &class.name, e.code, e.message
+                );
+            }
+        }
+    }
+
+    build_override_calls(&classes, &subtypes, &options.symbol_kinds)
+}
+
+/// Build calls from each class method to the closest method overriding it in each branch of the
+/// class hierarchy, given the direct subtypes of each class (as indices into `classes`)
+fn build_override_calls(
+    classes: &[(Url, lsp_types::DocumentSymbol)],
+    subtypes: &[Vec<usize>],
+    symbol_kinds: &[SymbolKind],
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let mut calls = vec![];
+    for (i, (file, class)) in classes.iter().enumerate() {
+        // interface methods are handled by the dispatch calls
+        if class.kind == SymbolKind::INTERFACE {
+            continue;
+        }
+
+        for method in get_class_methods(class, symbol_kinds) {
+            let source_item = build_definition_item(file, method);
+
+            let mut visited = HashSet::new();
+            let mut unvisited = subtypes[i].clone();
+            while let Some(j) = unvisited.pop() {
+                if !visited.insert(j) {
+                    continue;
+                }
+
+                let (subclass_file, subclass) = &classes[j];
+                match get_class_methods(subclass, symbol_kinds)
+                    .find(|overriding| is_same_signature(method, overriding))
+                {
+                    Some(overriding) => calls.push((
+                        source_item.clone(),
+                        build_definition_item(subclass_file, overriding),
+                    )),
+                    None => unvisited.extend(subtypes[j].iter().copied()),
+                }
+            }
+        }
     }
 
-    Ok(calls)
+    calls
+}
+
+/// Whether two methods have the same name and parameters, so that overloads aren't mixed up.
+/// jdtls puts the parameter types in the name (e.g. "parse(String)"), other servers put the
+/// signature in the detail (e.g. "fn parse(s: &str)"), or nothing at all.
+fn is_same_signature(
+    method: &lsp_types::DocumentSymbol,
+    other: &lsp_types::DocumentSymbol,
+) -> bool {
+    let signature = |symbol: &lsp_types::DocumentSymbol| {
+        let name = symbol.name.split('(').next().unwrap_or_default().trim();
+        let parameters = get_parameters(&symbol.name)
+            .or_else(|| symbol.detail.as_deref().and_then(get_parameters));
+
+        (name.to_string(), parameters.map(str::to_string))
+    };
+
+    let (name, parameters) = signature(method);
+    let (other_name, other_parameters) = signature(other);
+
+    name == other_name
+        && (parameters.is_none() || other_parameters.is_none() || parameters == other_parameters)
 }
 
-fn update_exact_definitions(
+/// Get the text between the first parenthesis of a signature and its matching one
+fn get_parameters(signature: &str) -> Option<&str> {
+    let start = signature.find('(')? + 1;
+
+    let mut depth = 1;
+    for (i, c) in signature[start..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            _ => {}
+        }
+
+        if depth == 0 {
+            return Some(signature[start..start + i].trim());
+        }
+    }
+
+    None
+}
+
+/// Fallback for servers without type hierarchy support, some servers (e.g. jdtls) return
+/// the overriding methods as the implementations of a class method
+async fn get_override_calls_from_implementations(
+    client: &mut LspClient,
+    classes: &[(Url, lsp_types::DocumentSymbol)],
+    project_root: &Url,
+    options: &CallGraphOptions,
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let methods = classes
+        .iter()
+        .flat_map(|(file, class)| {
+            get_class_methods(class, &options.symbol_kinds)
+                .map(move |method| (file.clone(), method.clone()))
+        })
+        .collect::<Vec<_>>();
+
+    let mut calls = vec![];
+    for (file, class) in classes.iter() {
+        if class.kind == SymbolKind::INTERFACE {
+            continue;
+        }
+
+        for method in get_class_methods(class, &options.symbol_kinds) {
+            let implementations = get_implementations(client, file, method, project_root).await;
+
+            calls.extend(build_implementation_calls(
+                file,
+                method,
+                &implementations,
+                &methods,
+            ));
+        }
+    }
+
+    calls
+}
+
+async fn get_subtypes(
+    client: &mut LspClient,
+    file: &Url,
+    class: &lsp_types::DocumentSymbol,
+) -> Result<Vec<TypeHierarchyItem>, LspError> {
+    let items = client
+        .prepare_type_hierarchy(file.clone(), class.selection_range.start)
+        .await?;
+
+    let Some(item) = items.and_then(|items| items.into_iter().next()) else {
+        return Ok(vec![]);
+    };
+
+    Ok(client
+        .type_hierarchy_subtypes(item)
+        .await?
+        .unwrap_or_default())
+}
+
+async fn get_implementations(
+    client: &mut LspClient,
+    file: &Url,
+    definition: &lsp_types::DocumentSymbol,
+    project_root: &Url,
+) -> Vec<(Url, Range)> {
+    let result = client
+        .implementation(file.clone(), definition.selection_range.start)
+        .await;
+
+    match result {
+        Ok(Some(response)) => get_locations(response),
+        Ok(None) => vec![],
+        Err(e) => {
+            debug!(
+                "got jsonRpcError for implementations of {:?}: {:?} {:?}",
+                (
+                    file.as_str().trim_start_matches(project_root.as_str()),
+                    &definition.name
+                ),
+                e.code,
+                e.message
+            );
+
+            vec![]
+        }
+    }
+}
+
+fn get_locations(response: GotoDefinitionResponse) -> Vec<(Url, Range)> {
+    match response {
+        GotoDefinitionResponse::Scalar(location) => vec![(location.uri, location.range)],
+        GotoDefinitionResponse::Array(locations) => locations
+            .into_iter()
+            .map(|location| (location.uri, location.range))
+            .collect(),
+        GotoDefinitionResponse::Link(links) => links
+            .into_iter()
+            .map(|link| (link.target_uri, link.target_selection_range))
+            .collect(),
+    }
+}
+
+async fn get_document_symbols(
+    client: &mut LspClient,
+    file: &Url,
+) -> Result<Vec<lsp_types::DocumentSymbol>, Box<dyn Error>> {
+    let mut retries_left = DOCUMENT_SYMBOL_RETRIES;
+
+    // right after a change (e.g. in watch mode) the server may not have caught up with the file
+    let result = loop {
+        match client.document_symbol(file.clone()).await {
+            Ok(Some(result)) => break result,
+            // a file without any symbols
+            Ok(None) => return Ok(vec![]),
+            Err(e) if e.code != CONTENT_MODIFIED => {
+                return Err(format!("got jsonRpcError: {:?} {:?}", e.code, e.message).into());
+            }
+            Err(_) if retries_left == 0 => return Err("max retries exceeded".into()),
+            Err(_) => {
+                retries_left -= 1;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    };
+
+    match result {
+        // we need DocumentSymbol for the precise location of the function name
+        lsp_types::DocumentSymbolResponse::Flat(_) => Err("got flat".into()),
+        lsp_types::DocumentSymbolResponse::Nested(symbols) => Ok(symbols),
+    }
+}
+
+/// Get calls from `source_item` that aren't found by querying the incoming calls of changed files
+async fn get_outgoing_calls(
+    client: &mut LspClient,
+    source_item: &CallHierarchyItem,
+    project_root: &Url,
+    exact_definitions: &[(Url, lsp_types::DocumentSymbol)],
+    changed_files: &HashSet<Url>,
+    include_external: bool,
+) -> Vec<(CallHierarchyItem, CallHierarchyItem)> {
+    let result = client
+        .call_hierarchy_outgoing_calls(source_item.clone())
+        .await;
+
+    let response = match result {
+        Ok(Some(response)) => response,
+        Ok(None) => return vec![],
+        Err(e) => {
+            debug!(
+                "got jsonRpcError for outgoing calls of {:?}: {:?} {:?}",
+                &source_item.name, e.code, e.message
+            );
+
+            return vec![];
+        }
+    };
+
+    let mut calls = vec![];
+    for target_item in response {
+        let target = target_item.to;
+
+        if !target.uri.as_str().starts_with(project_root.as_str()) {
+            if include_external {
+                calls.push((source_item.clone(), target));
+            }
+        } else if !changed_files.contains(&target.uri) {
+            // use the same item as the incoming calls would have
+            if let Some((file, definition)) =
+                find_enclosing_definition(exact_definitions, &target.uri, &target.selection_range)
+            {
+                calls.push((source_item.clone(), build_definition_item(file, definition)));
+            }
+        }
+    }
+
+    calls
+}
+
+pub(crate) fn update_exact_definitions(
     symbols: Vec<lsp_types::DocumentSymbol>,
     file: &Url,
+    symbol_kinds: &[SymbolKind],
     exact_definitions: &mut Vec<(Url, lsp_types::DocumentSymbol)>,
 ) {
     for symbol in symbols {
-        if symbol.kind == SymbolKind::FUNCTION || symbol.kind == SymbolKind::METHOD {
+        if symbol_kinds.contains(&symbol.kind) {
             exact_definitions.push((file.to_owned(), symbol.clone()));
         }
 
         if let Some(children) = symbol.children {
-            update_exact_definitions(children, file, exact_definitions);
+            update_exact_definitions(children, file, symbol_kinds, exact_definitions);
         }
     }
 }
 
-pub fn get_function_depths(
-    calls: Vec<(CallHierarchyItem, CallHierarchyItem)>,
-) -> Vec<(CallHierarchyItem, Vec<Vec<CallHierarchyItem>>)> {
-    // convert call items into hashable call items
-    let hashable_calls = calls
-        .iter()
-        .map(|(s, t)| (s.clone().into(), t.clone().into()))
-        .collect::<Vec<(HashableCallHierarchyItem, HashableCallHierarchyItem)>>();
+/// Collect methods declared directly inside traits / interfaces
+fn update_interface_methods(
+    symbols: &[lsp_types::DocumentSymbol],
+    file: &Url,
+    symbol_kinds: &[SymbolKind],
+    interface_methods: &mut Vec<(Url, lsp_types::DocumentSymbol)>,
+) {
+    for symbol in symbols {
+        let Some(children) = &symbol.children else {
+            continue;
+        };
+
+        if symbol.kind == SymbolKind::INTERFACE {
+            for child in children {
+                if symbol_kinds.contains(&child.kind) {
+                    interface_methods.push((file.to_owned(), child.clone()));
+                }
+            }
+        }
 
-    let depths_by_root = get_depths(&hashable_calls);
+        update_interface_methods(children, file, symbol_kinds, interface_methods);
+    }
+}
 
-    // get item paths from each root
-    let mut item_paths_from_roots = HashMap::new();
-    for (_, items) in depths_by_root {
-        for (item, item_path) in items {
-            let item_path_from_root: &mut Vec<Vec<CallHierarchyItem>> =
-                item_paths_from_roots.entry(item).or_default();
+/// Collect methods declared directly inside trait impl blocks
+fn update_trait_impl_methods(
+    symbols: &[lsp_types::DocumentSymbol],
+    file: &Url,
+    symbol_kinds: &[SymbolKind],
+    trait_impl_methods: &mut Vec<(Url, lsp_types::DocumentSymbol)>,
+) {
+    for symbol in symbols {
+        let Some(children) = &symbol.children else {
+            continue;
+        };
 
-            let mut converted_item_path: Vec<CallHierarchyItem> = vec![];
-            for hop in item_path {
-                converted_item_path.push(hop.into());
+        // rust-analyzer names trait impl blocks like "impl Debug for A"
+        if symbol.name.starts_with("impl") && symbol.name.contains(" for ") {
+            for child in children {
+                if symbol_kinds.contains(&child.kind) {
+                    trait_impl_methods.push((file.to_owned(), child.clone()));
+                }
             }
+        }
+
+        update_trait_impl_methods(children, file, symbol_kinds, trait_impl_methods);
+    }
+}
+
+/// Collect all class-like symbols, including nested ones
+fn update_classes(
+    symbols: &[lsp_types::DocumentSymbol],
+    file: &Url,
+    classes: &mut Vec<(Url, lsp_types::DocumentSymbol)>,
+) {
+    for symbol in symbols {
+        if [
+            SymbolKind::CLASS,
+            SymbolKind::STRUCT,
+            SymbolKind::ENUM,
+            SymbolKind::INTERFACE,
+        ]
+        .contains(&symbol.kind)
+        {
+            classes.push((file.to_owned(), symbol.clone()));
+        }
 
-            item_path_from_root.push(converted_item_path);
+        if let Some(children) = &symbol.children {
+            update_classes(children, file, classes);
         }
     }
+}
+
+fn get_class_methods<'a>(
+    class: &'a lsp_types::DocumentSymbol,
+    symbol_kinds: &'a [SymbolKind],
+) -> impl Iterator<Item = &'a lsp_types::DocumentSymbol> {
+    class
+        .children
+        .iter()
+        .flatten()
+        .filter(|child| symbol_kinds.contains(&child.kind))
+}
+
+fn build_definition_item(file: &Url, definition: &lsp_types::DocumentSymbol) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: definition.name.clone(),
+        kind: definition.kind,
+        tags: definition.tags.clone(),
+        detail: definition.detail.clone(),
+        uri: file.clone(),
+        range: definition.range,
+        selection_range: definition.selection_range,
+        data: None,
+    }
+}
+
+/// Build the item of a method declared at `uri` / `range`, e.g. a trait method outside the project
+fn build_declaration_item(
+    uri: &Url,
+    range: Range,
+    method: &lsp_types::DocumentSymbol,
+) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: method.name.clone(),
+        kind: method.kind,
+        tags: None,
+        detail: None,
+        uri: uri.clone(),
+        range,
+        selection_range: range,
+        data: None,
+    }
+}
 
-    item_paths_from_roots
+/// Find the innermost definition whose range contains `range`
+fn find_enclosing_definition<'a>(
+    definitions: &'a [(Url, lsp_types::DocumentSymbol)],
+    uri: &Url,
+    range: &Range,
+) -> Option<&'a (Url, lsp_types::DocumentSymbol)> {
+    definitions
+        .iter()
+        .filter(|(file, definition)| {
+            file == uri
+                && definition.range.start <= range.start
+                && range.end <= definition.range.end
+        })
+        .max_by_key(|(_, definition)| definition.range.start)
+}
+
+pub fn get_function_depths(graph: &CallGraph<SymbolId>) -> Depths<SymbolId> {
+    group_depths_by_item(get_depths(graph))
+}
+
+/// Get depths starting only from the given entry points, instead of from inferred roots
+pub fn get_function_depths_from_entries(
+    graph: &CallGraph<SymbolId>,
+    entries: &[SymbolId],
+) -> Depths<SymbolId> {
+    group_depths_by_item(get_depths_from_entries(graph, entries))
+}
+
+/// Get the longest path to each function (its maximum depth), starting from the given
+/// entry points, or from inferred roots if there are none
+pub fn get_max_function_depths(
+    graph: &CallGraph<SymbolId>,
+    entries: Option<&[SymbolId]>,
+) -> MaxDepths<SymbolId> {
+    get_max_depths(graph, entries)
+}
+
+/// Get the functions reachable from any of the given entry points, including the entries
+/// that have calls
+pub fn get_reachable_functions(
+    graph: &CallGraph<SymbolId>,
+    entries: &[SymbolId],
+) -> HashSet<SymbolId> {
+    get_reachable(graph, entries)
+}
+
+/// Get the number of functions each function dominates (every call chain from an entry point
+/// to them passes through it), starting from the given entry points, or from inferred roots
+/// if there are none
+pub fn get_dominated_function_counts(
+    graph: &CallGraph<SymbolId>,
+    entries: Option<&[SymbolId]>,
+) -> Vec<(SymbolId, usize)> {
+    get_dominated_sizes(graph, entries)
+}
+
+/// Get fan-in / fan-out, transitive callers / callees and betweenness centrality of each function
+pub fn get_function_metrics(graph: &CallGraph<SymbolId>) -> Vec<FunctionMetrics<SymbolId>> {
+    get_metrics(graph)
+}
+
+/// Get up to `max_paths` call paths between two functions, shortest first
+pub fn get_call_paths(
+    graph: &CallGraph<SymbolId>,
+    from: SymbolId,
+    to: SymbolId,
+    max_paths: usize,
+) -> Vec<Vec<SymbolId>> {
+    get_paths(graph, &from, &to, max_paths)
+}
+
+/// Get the tree of functions (transitively) calling `item`, up to `max_depth` levels
+pub fn get_callers_tree(
+    graph: &CallGraph<SymbolId>,
+    item: SymbolId,
+    max_depth: usize,
+) -> CallTree<SymbolId> {
+    get_call_tree(graph, &item, max_depth, petgraph::Direction::Incoming)
+}
+
+/// Get the tree of functions (transitively) called by `item`, up to `max_depth` levels
+pub fn get_callees_tree(
+    graph: &CallGraph<SymbolId>,
+    item: SymbolId,
+    max_depth: usize,
+) -> CallTree<SymbolId> {
+    get_call_tree(graph, &item, max_depth, petgraph::Direction::Outgoing)
+}
+
+/// Get the locations of the calls from `caller` to `callee`, inside `caller`
+pub async fn get_call_sites(
+    client: &mut LspClient,
+    caller: &CallHierarchyItem,
+    callee: &CallHierarchyItem,
+) -> Vec<Location> {
+    let response = match client.call_hierarchy_outgoing_calls(caller.clone()).await {
+        Ok(Some(response)) => response,
+        Ok(None) => return vec![],
+        Err(e) => {
+            debug!(
+                "got jsonRpcError for outgoing calls of {:?}: {:?} {:?}",
+                &caller.name, e.code, e.message
+            );
+
+            return vec![];
+        }
+    };
+
+    response
         .into_iter()
-        .map(|(k, v)| (k.into(), v))
+        .filter(|call| {
+            call.to.uri == callee.uri
+                && callee.range.start <= call.to.selection_range.start
+                && call.to.selection_range.end <= callee.range.end
+        })
+        .flat_map(|call| call.from_ranges)
+        .map(|range| Location::new(caller.uri.clone(), range))
+        .collect()
+}
+
+/// Get all definitions found in the project, including functions that aren't part of any call
+pub fn get_definition_items(cache: &AnalysisCache) -> Vec<CallHierarchyItem> {
+    cache
+        .definitions()
+        .iter()
+        .map(|(file, definition)| build_definition_item(file, definition))
         .collect()
 }
 
+/// Get depths of aggregated calls, e.g. calls between files (see [`aggregate::aggregate_calls`])
+pub fn get_aggregated_depths(calls: &[(String, String)]) -> Depths<String> {
+    group_depths_by_item(get_depths(&CallGraph::new(calls)))
+}
+
+fn group_depths_by_item<T: Hash + Eq>(depths_by_root: DepthsByRoot<T>) -> Depths<T> {
+    // get item paths from each root
+    let mut item_paths_from_roots: HashMap<T, Vec<CallPath<T>>> = HashMap::new();
+    for (_, items) in depths_by_root {
+        for (item, item_path) in items {
+            item_paths_from_roots
+                .entry(item)
+                .or_default()
+                .push(item_path);
+        }
+    }
+
+    item_paths_from_roots.into_iter().collect()
+}
+
+/// Suggest calls to remove or invert to make the call graph acyclic, with the number of cycles
+/// each of them breaks, most first
+pub fn get_cycle_breaks(graph: &CallGraph<SymbolId>) -> Vec<FeedbackArc<SymbolId>> {
+    let mut arcs = get_feedback_arcs(graph, MAX_COUNTED_CYCLES, MAX_CYCLE_SEARCH_STEPS);
+    arcs.sort_by_key(|arc| std::cmp::Reverse(arc.cycles));
+
+    arcs
+}
+
+/// Find groups of functions that are (mutually) recursive
+pub fn get_recursive_clusters(graph: &CallGraph<SymbolId>) -> Vec<RecursiveCluster<SymbolId>> {
+    graph.recursive_clusters()
+}
+
 pub fn build_short_fn_depths(
+    arena: &SymbolArena,
     root: &Url,
-    depths: &Vec<(CallHierarchyItem, Vec<Vec<CallHierarchyItem>>)>,
+    depths: &Depths<SymbolId>,
 ) -> Depths<String> {
+    let names = arena.names(root);
     let mut short_item_depths = vec![];
 
     for (item, paths_from_roots) in depths {
-        let item_name = build_call_hierarchy_item_name(item, root);
+        let item_name = names.get(*item).to_string();
 
         let mut short_paths = vec![];
         for path in paths_from_roots {
             let mut short_path = vec![];
-            for hop in path {
-                short_path.push(build_call_hierarchy_item_name(hop, root));
+            for hop in &path.path {
+                short_path.push(names.get(*hop).to_string());
             }
 
-            short_paths.push(short_path);
+            short_paths.push(CallPath {
+                depth: path.depth,
+                path: short_path,
+            });
         }
 
         short_item_depths.push((item_name, short_paths));
@@ -305,31 +1082,72 @@ pub fn build_short_fn_depths(
 }
 
 pub fn build_call_hierarchy_item_name(item: &CallHierarchyItem, root: &Url) -> String {
+    let name = item.name.split('(').next().unwrap();
+
+    // items outside the project are labeled by their crate / package / jar
+    if !item.uri.as_str().starts_with(root.as_str()) {
+        return format!(
+            "[{}]:{}",
+            external::get_external_package_name(&item.uri),
+            name
+        );
+    }
+
     format!(
         "{}:{}",
         item.uri.as_str().trim_start_matches(root.as_str()),
-        item.name.split('(').next().unwrap()
+        name
     )
 }
 
-pub type Depths<T> = Vec<(T, Vec<Vec<T>>)>;
-pub fn find_items_with_different_depths<T, H>(depths: &Depths<T>) -> HashSet<H>
+/// Paths from each root to each item
+pub type Depths<T> = Vec<(T, Vec<CallPath<T>>)>;
+/// Longest path from a root to each item
+pub type MaxDepths<T> = Vec<(T, CallPath<T>)>;
+
+/// Find items reachable through disjoint paths of different lengths. If `max_depths` is given,
+/// items whose shortest path is shorter than their longest path are included too.
+pub fn find_items_with_different_depths<T, H>(
+    depths: &Depths<T>,
+    max_depths: Option<&MaxDepths<T>>,
+) -> HashSet<H>
 where
     T: PartialEq + Into<H> + Clone,
     H: Hash + Eq,
 {
+    let min_depths = depths
+        .iter()
+        .filter_map(|(item, paths)| {
+            let min_depth = paths.iter().map(|path| path.depth).min()?;
+
+            Some((item.clone().into(), min_depth))
+        })
+        .collect::<HashMap<H, _>>();
+
+    let max_depth_problems = max_depths
+        .into_iter()
+        .flatten()
+        .map(|(item, max_path)| (item.clone().into(), max_path.depth))
+        .filter(|(item, max_depth)| {
+            min_depths
+                .get(item)
+                .is_some_and(|min_depth| min_depth < max_depth)
+        })
+        .map(|(item, _)| item)
+        .collect::<Vec<_>>();
+
     depths
         .iter()
         .filter(|(item, item_paths_from_roots)| {
             let total_unique_depths = item_paths_from_roots
                 .iter()
-                .map(|path| path.len())
+                .map(|path| path.depth)
                 .collect::<HashSet<_>>()
                 .len();
 
             let mut all_hops: HashSet<H> = HashSet::new();
             let paths_are_unique = item_paths_from_roots.iter().all(|path| {
-                path.iter().filter(|&hop| hop != item).all(|hop| {
+                path.path.iter().filter(|&hop| hop != item).all(|hop| {
                     let h_hop: H = hop.clone().into();
                     all_hops.insert(h_hop)
                 })
@@ -338,5 +1156,204 @@ where
             total_unique_depths > 1 && paths_are_unique
         })
         .map(|(item, _)| item.clone().into())
+        .chain(max_depth_problems)
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::{DocumentSymbol, Position, Range, SymbolKind, Url};
+
+    use super::{
+        build_implementation_calls, build_override_calls, find_enclosing_definition,
+        get_parameters, update_interface_methods, update_trait_impl_methods,
+    };
+
+    fn range(start_line: u32, end_line: u32) -> Range {
+        Range::new(Position::new(start_line, 0), Position::new(end_line, 0))
+    }
+
+    #[allow(deprecated)]
+    fn symbol(name: &str, range: Range) -> DocumentSymbol {
+        DocumentSymbol {
+            name: name.to_string(),
+            detail: None,
+            kind: SymbolKind::FUNCTION,
+            tags: None,
+            deprecated: None,
+            range,
+            selection_range: range,
+            children: None,
+        }
+    }
+
+    fn container(
+        name: &str,
+        kind: SymbolKind,
+        range: Range,
+        children: Vec<DocumentSymbol>,
+    ) -> DocumentSymbol {
+        DocumentSymbol {
+            kind,
+            children: Some(children),
+            ..symbol(name, range)
+        }
+    }
+
+    #[test]
+    fn test_update_interface_and_trait_impl_methods() {
+        let file = Url::parse("file:///project/src/main.rs").unwrap();
+        let symbols = vec![
+            container(
+                "Shape",
+                SymbolKind::INTERFACE,
+                range(0, 3),
+                vec![symbol("area", range(1, 2))],
+            ),
+            container(
+                "impl Shape for Square",
+                SymbolKind::OBJECT,
+                range(4, 7),
+                vec![symbol("area", range(5, 6))],
+            ),
+            container(
+                "impl std::fmt::Debug for Square",
+                SymbolKind::OBJECT,
+                range(8, 11),
+                vec![symbol("fmt", range(9, 10))],
+            ),
+            container(
+                "impl Square",
+                SymbolKind::OBJECT,
+                range(12, 15),
+                vec![symbol("new", range(13, 14))],
+            ),
+        ];
+        let kinds = [SymbolKind::FUNCTION];
+        let starts = |methods: &[(Url, DocumentSymbol)]| {
+            methods
+                .iter()
+                .map(|(_, m)| m.range.start.line)
+                .collect::<Vec<_>>()
+        };
+
+        let mut interface_methods = vec![];
+        update_interface_methods(&symbols, &file, &kinds, &mut interface_methods);
+        assert_eq!(starts(&interface_methods), vec![1]);
+
+        // inherent impl methods can't be dispatched to
+        let mut trait_impl_methods = vec![];
+        update_trait_impl_methods(&symbols, &file, &kinds, &mut trait_impl_methods);
+        assert_eq!(starts(&trait_impl_methods), vec![5, 9]);
+    }
+
+    #[test]
+    fn test_build_implementation_calls() {
+        let file = Url::parse("file:///project/src/main.rs").unwrap();
+        let method = symbol("area", range(1, 2));
+        let definitions = vec![
+            (file.clone(), method.clone()),
+            (file.clone(), symbol("area", range(5, 6))),
+        ];
+
+        // the method itself (default implementation) and locations outside any definition
+        // are skipped
+        let calls = build_implementation_calls(
+            &file,
+            &method,
+            &[
+                (file.clone(), range(1, 1)),
+                (file.clone(), range(5, 5)),
+                (file.clone(), range(20, 20)),
+            ],
+            &definitions,
+        );
+
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].0.range, range(1, 2));
+        assert_eq!(calls[0].1.range, range(5, 6));
+    }
+
+    #[test]
+    fn test_get_parameters() {
+        assert_eq!(get_parameters("parse(String, int)"), Some("String, int"));
+        assert_eq!(
+            get_parameters("fn apply(f: impl Fn(u32) -> u32) -> u32"),
+            Some("f: impl Fn(u32) -> u32")
+        );
+        assert_eq!(get_parameters("parse"), None);
+    }
+
+    #[test]
+    fn test_build_override_calls() {
+        let file = Url::parse("file:///project/src/Parser.java").unwrap();
+        let classes = vec![
+            (
+                file.clone(),
+                container(
+                    "Parser",
+                    SymbolKind::CLASS,
+                    range(0, 10),
+                    vec![
+                        symbol("parse(String)", range(1, 2)),
+                        symbol("parse(int)", range(3, 4)),
+                        symbol("close()", range(5, 6)),
+                    ],
+                ),
+            ),
+            (
+                file.clone(),
+                container(
+                    "JsonParser",
+                    SymbolKind::CLASS,
+                    range(11, 20),
+                    vec![symbol("parse(int)", range(12, 13))],
+                ),
+            ),
+            (
+                file.clone(),
+                container(
+                    "StrictJsonParser",
+                    SymbolKind::CLASS,
+                    range(21, 30),
+                    vec![
+                        symbol("parse(String)", range(22, 23)),
+                        symbol("parse(int)", range(24, 25)),
+                    ],
+                ),
+            ),
+        ];
+        let subtypes = vec![vec![1], vec![2], vec![]];
+
+        let calls = build_override_calls(&classes, &subtypes, &[SymbolKind::FUNCTION])
+            .into_iter()
+            .map(|(from, to)| (from.range.start.line, to.range.start.line))
+            .collect::<Vec<_>>();
+
+        // overloads are told apart, and each method is linked to its closest override
+        assert_eq!(calls, vec![(1, 22), (3, 12), (12, 24)]);
+    }
+
+    #[test]
+    fn test_find_enclosing_definition() {
+        let file = Url::parse("file:///project/src/main.rs").unwrap();
+        let other_file = Url::parse("file:///project/src/other.rs").unwrap();
+
+        let definitions = vec![
+            (file.clone(), symbol("outer", range(0, 10))),
+            (file.clone(), symbol("inner", range(2, 5))),
+            (file.clone(), symbol("after", range(12, 14))),
+        ];
+
+        let enclosing = |uri: &Url, range: Range| {
+            find_enclosing_definition(&definitions, uri, &range).map(|(_, d)| d.name.as_str())
+        };
+
+        // a closure inside a nested function is attributed to the innermost one
+        assert_eq!(enclosing(&file, range(3, 4)), Some("inner"));
+        assert_eq!(enclosing(&file, range(6, 7)), Some("outer"));
+        assert_eq!(enclosing(&file, range(12, 14)), Some("after"));
+        assert_eq!(enclosing(&file, range(10, 11)), None);
+        assert_eq!(enclosing(&other_file, range(3, 4)), None);
+    }
+}
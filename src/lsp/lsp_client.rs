@@ -1,13 +1,22 @@
 use log::{debug, error, warn};
 use lsp_types::{
-    notification::{Initialized, Notification},
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Initialized, Notification,
+    },
     request::{
-        CallHierarchyIncomingCalls, DocumentSymbolRequest, Initialize, Request, WorkspaceSymbol,
+        CallHierarchyIncomingCalls, CallHierarchyOutgoingCalls, DocumentSymbolRequest,
+        GotoDeclaration, GotoDeclarationParams, GotoDeclarationResponse, GotoImplementation,
+        GotoImplementationParams, GotoImplementationResponse, Request, WorkspaceSymbol,
     },
     CallHierarchyIncomingCall, CallHierarchyIncomingCallsParams, CallHierarchyItem,
+    CallHierarchyOutgoingCall, CallHierarchyOutgoingCallsParams, DidChangeTextDocumentParams,
+    DidCloseTextDocumentParams, DidOpenTextDocumentParams, DidSaveTextDocumentParams,
     DocumentSymbolParams, DocumentSymbolResponse, InitializeParams, InitializeResult,
-    InitializedParams, PartialResultParams, SymbolInformation, TextDocumentIdentifier, Url,
-    WorkDoneProgressParams, WorkspaceSymbolParams,
+    InitializedParams, PartialResultParams, Position, SymbolInformation,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier, WorkDoneProgressParams,
+    WorkspaceSymbolParams,
 };
 use serde_json::{json, Value};
 use tokio::{
@@ -18,7 +27,13 @@ use tokio::{
 
 use crate::lsp::json_rpc::{LspResponse, ResponseContents};
 
-use super::json_rpc::{self, build_notification, build_request, LspError};
+use super::{
+    json_rpc::{self, build_notification, build_request, LspError},
+    type_hierarchy::{
+        InitializeWithTypeHierarchy, TypeHierarchyItem, TypeHierarchyPrepare,
+        TypeHierarchyPrepareParams, TypeHierarchySubtypes, TypeHierarchySubtypesParams,
+    },
+};
 
 pub struct LspClient {
     to_server: mpsc::UnboundedSender<Vec<u8>>,
@@ -122,7 +137,12 @@ impl LspClient {
         &mut self,
         params: &InitializeParams,
     ) -> Result<InitializeResult, LspError> {
-        let result = self.call::<Initialize>(params).await?;
+        // lsp_types has no field for the type hierarchy capability yet
+        let mut params = serde_json::to_value(params).expect("failed to serialize params");
+        params["capabilities"]["textDocument"]["typeHierarchy"] =
+            json!({ "dynamicRegistration": false });
+
+        let result = self.call::<InitializeWithTypeHierarchy>(&params).await?;
 
         self.notify::<Initialized>(&InitializedParams {}).await;
 
@@ -166,6 +186,126 @@ impl LspClient {
 
         self.call::<CallHierarchyIncomingCalls>(&params).await
     }
+
+    pub async fn call_hierarchy_outgoing_calls(
+        &mut self,
+        item: CallHierarchyItem,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>, LspError> {
+        let params = CallHierarchyOutgoingCallsParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        self.call::<CallHierarchyOutgoingCalls>(&params).await
+    }
+
+    pub async fn implementation(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<GotoImplementationResponse>, LspError> {
+        let params = GotoImplementationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        self.call::<GotoImplementation>(&params).await
+    }
+
+    pub async fn declaration(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<GotoDeclarationResponse>, LspError> {
+        let params = GotoDeclarationParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        self.call::<GotoDeclaration>(&params).await
+    }
+
+    pub async fn prepare_type_hierarchy(
+        &mut self,
+        uri: Url,
+        position: Position,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>, LspError> {
+        let params = TypeHierarchyPrepareParams {
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+
+        self.call::<TypeHierarchyPrepare>(&params).await
+    }
+
+    pub async fn type_hierarchy_subtypes(
+        &mut self,
+        item: TypeHierarchyItem,
+    ) -> Result<Option<Vec<TypeHierarchyItem>>, LspError> {
+        let params = TypeHierarchySubtypesParams {
+            item,
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+
+        self.call::<TypeHierarchySubtypes>(&params).await
+    }
+
+    pub async fn did_open(&mut self, uri: Url, language_id: &str, version: i32, text: String) {
+        let params = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem {
+                uri,
+                language_id: language_id.to_string(),
+                version,
+                text,
+            },
+        };
+
+        self.notify::<DidOpenTextDocument>(&params).await
+    }
+
+    pub async fn did_change(&mut self, uri: Url, version: i32, text: String) {
+        let params = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier { uri, version },
+            // send the full content, no need to compute incremental changes
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text,
+            }],
+        };
+
+        self.notify::<DidChangeTextDocument>(&params).await
+    }
+
+    pub async fn did_save(&mut self, uri: Url) {
+        let params = DidSaveTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri },
+            text: None,
+        };
+
+        self.notify::<DidSaveTextDocument>(&params).await
+    }
+
+    pub async fn did_close(&mut self, uri: Url) {
+        let params = DidCloseTextDocumentParams {
+            text_document: TextDocumentIdentifier { uri },
+        };
+
+        self.notify::<DidCloseTextDocument>(&params).await
+    }
 }
 
 pub fn start_io_threads(
@@ -179,7 +319,6 @@ pub fn start_io_threads(
         let stdin = server
             .stdin
             .as_mut()
-            .take()
             .expect("failed to acquire stdout of server process");
 
         while let Some(buf) = to_server_receiver.recv().await {
@@ -198,7 +337,6 @@ pub fn start_io_threads(
             let stdout = server
                 .stdout
                 .as_mut()
-                .take()
                 .expect("failed to acquire stdout of server process");
 
             while let Ok(buf) = json_rpc::get_next_response(stdout).await {
@@ -214,7 +352,6 @@ pub fn start_io_threads(
             let stderr = server
                 .stderr
                 .as_mut()
-                .take()
                 .expect("failed to acquire stderr of server process");
 
             let mut buf = vec![];
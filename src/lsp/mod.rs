@@ -1,4 +1,5 @@
 pub mod json_rpc;
 pub mod lsp_client;
+pub mod type_hierarchy;
 
 pub use lsp_client::LspClient;
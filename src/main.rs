@@ -1,32 +1,292 @@
-use std::{collections::HashSet, path::PathBuf, process::Stdio, time::Duration};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::PathBuf,
+    process::Stdio,
+    time::Duration,
+};
 
-use clap::Parser;
-use log::LevelFilter;
-use lsp_types::{CallHierarchyItem, Url};
+use clap::{error::ErrorKind, CommandFactory, Parser, Subcommand, ValueEnum};
+use log::{info, warn, LevelFilter};
+use lsp_types::{CallHierarchyItem, Location, SymbolKind, Url};
 use regex::Regex;
 use serde_json::{json, Value};
 use tokio::process::{Child, Command};
 
-use code_depth::{hashable_call_hierarchy_item::HashableCallHierarchyItem, lsp::LspClient, Depths};
+use code_depth::{
+    abstraction::{self, MixedAbstraction},
+    aggregate::{self, Granularity},
+    baseline::Baseline,
+    cache::AnalysisCache,
+    dead_code::{self, DeadCodeOptions},
+    diff::{diff_snapshots, Snapshot, Worktree},
+    dsm::Dsm,
+    entry::{self, EntryKind, EntryPattern},
+    explain,
+    language::Language,
+    layers::{LayerViolation, Layers},
+    lsp::LspClient,
+    suppression,
+    symbol_arena::{SymbolArena, SymbolId, SymbolNames},
+    watch::{self, FileChange, FileWatcher},
+    CallGraph, CallGraphOptions, CallPath, CallTree, Depths, FeedbackArc, FunctionMetrics,
+    MaxDepths, RecursiveCluster,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    #[command(subcommand)]
+    command: Option<SubCommand>,
+
     #[arg(short, long)]
     project_path: PathBuf,
 
+    /// Required by all commands except clear-cache
     #[arg(short, long)]
-    lang_server_exe: String,
+    lang_server_exe: Option<String>,
 
     #[arg(short, long, default_value = ".*test.*")]
     ignore_re: Option<String>,
 
+    /// Symbol kinds to treat as functions, e.g. "Function,Method,Constructor"
+    /// (default: depends on the lsp server language)
+    #[arg(short, long, value_delimiter = ',', value_parser = parse_symbol_kind)]
+    symbol_kinds: Option<Vec<SymbolKind>>,
+
+    /// Include calls into dependencies and the standard library as leaf nodes
+    #[arg(short = 'e', long)]
+    include_external: bool,
+
+    /// Add "dispatch" calls from trait / interface methods to their implementations
+    #[arg(short, long)]
+    dispatch: bool,
+
+    /// Add "override" calls from class methods to the methods overriding them in subclasses
+    #[arg(short, long)]
+    overrides: bool,
+
+    /// Reuse results of previous runs for unchanged files (stored in <project_path>/.code_depth)
+    #[arg(short, long)]
+    cache: bool,
+
+    /// Entry point to start from instead of inferred roots (can be repeated). Either a regex
+    /// over the function name, e.g. "src/main.rs:main$", or a symbol kind and a path, e.g.
+    /// "Method:src/api"
+    #[arg(long = "entry")]
+    entries: Vec<EntryPattern>,
+
+    /// Detect entry points (main functions, tests, public library items, servlet handlers, ...)
+    /// and start from them instead of inferred roots, together with any --entry patterns
+    #[arg(long)]
+    detect_entries: bool,
+
+    /// Also compute the longest path to each function, and report functions whose shortest and
+    /// longest paths differ as problems
+    #[arg(short, long)]
+    max_depth: bool,
+
+    /// Json file declaring the architecture layers, from top to bottom, e.g.
+    /// {"layers": [{"name": "api", "paths": ["api/**"]}, {"name": "service", "paths": ["service/**"]}]}.
+    /// Calls to higher layers or skipping a layer are reported, and fail the run
+    #[arg(long)]
+    layers: Option<PathBuf>,
+
+    /// Compute the dominator tree of all roots, and report functions (other than roots) that
+    /// dominate at least this many functions as chokepoints
+    #[arg(long, value_name = "MIN_DOMINATED")]
+    chokepoints: Option<usize>,
+
+    /// Add fan-in / fan-out, transitive callers / callees and betweenness centrality of each
+    /// function to the report
+    #[arg(long)]
+    metrics: bool,
+
+    /// Functions to leave out of the unreachable section, since they may be called from outside
+    /// of the project, e.g. "trait-impls,exported"
+    #[arg(long, value_enum, value_delimiter = ',')]
+    unreachable_exclude: Vec<UnreachableExclusion>,
+
+    /// Collapse calls between functions into calls between files, modules (directories) or
+    /// packages, and report depths of those instead
+    #[arg(long, value_enum)]
+    aggregate: Option<Granularity>,
+
+    /// Suggest calls to remove or invert to make the call graph acyclic
+    #[arg(long)]
+    break_cycles: bool,
+
+    /// Report functions whose direct callees' levels (longest call chain below them) differ by
+    /// at least this much, mixing high level orchestration with low level details
+    #[arg(long, value_name = "MIN_SPREAD")]
+    mixed_abstraction: Option<usize>,
+
+    /// Write the current problems to a baseline file
+    #[arg(long, value_name = "FILE")]
+    write_baseline: Option<PathBuf>,
+
+    /// Only fail the run for problems that are new or got worse compared to a baseline file
+    #[arg(long, value_name = "FILE")]
+    baseline: Option<PathBuf>,
+
     #[arg(short, long, action = clap::ArgAction::Count)]
     verbose: u8,
 }
 
+#[derive(Subcommand, Debug)]
+enum SubCommand {
+    /// Remove the analysis cache of the project
+    ClearCache,
+    /// Keep the lsp server running and print an updated report whenever files change
+    Watch {
+        /// How often to check for changed files
+        #[arg(long, default_value_t = 500)]
+        interval_ms: u64,
+    },
+    /// Compare the depths of two git revisions of the project
+    Diff {
+        /// Base revision, e.g. "main"
+        base: String,
+        /// Head revision, e.g. "HEAD"
+        head: String,
+    },
+    /// Rank functions by a call graph metric
+    Rank {
+        #[arg(long, value_enum, default_value_t = Metric::FanIn)]
+        by: Metric,
+        /// How many functions to list
+        #[arg(long, default_value_t = 20)]
+        top: usize,
+    },
+    /// List the shortest call path between two functions. Functions are given like --entry
+    /// patterns, e.g. "src/main.rs:main$"
+    Path {
+        from: EntryPattern,
+        to: EntryPattern,
+        /// List up to this many paths, shortest first, instead of only the shortest one
+        #[arg(long, value_name = "MAX_PATHS")]
+        all: Option<usize>,
+    },
+    /// Print the tree of functions (transitively) calling a function
+    Callers {
+        function: EntryPattern,
+        /// How many levels of callers to print
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+    },
+    /// Print the tree of functions (transitively) called by a function
+    Callees {
+        function: EntryPattern,
+        /// How many levels of callees to print
+        #[arg(long, default_value_t = 3)]
+        depth: usize,
+    },
+    /// Show why a function is reached at different depths
+    Explain { function: EntryPattern },
+    /// Print the dependency structure matrix of calls between files / modules / packages
+    Dsm {
+        #[arg(long, value_enum, default_value_t = Granularity::Module)]
+        granularity: Granularity,
+        #[arg(long, value_enum, default_value_t = DsmFormat::Csv)]
+        format: DsmFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DsmFormat {
+    Csv,
+    /// Standalone html heatmap
+    Html,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum UnreachableExclusion {
+    /// Trait impl methods (Rust), or overriding methods (Java)
+    TraitImpls,
+    /// Public items of lib crates (Rust), or public methods (Java)
+    Exported,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Metric {
+    FanIn,
+    FanOut,
+    TransitiveCallers,
+    TransitiveCallees,
+    Betweenness,
+}
+
+impl Metric {
+    fn get(&self, metrics: &FunctionMetrics<SymbolId>) -> f64 {
+        match self {
+            Metric::FanIn => metrics.fan_in as f64,
+            Metric::FanOut => metrics.fan_out as f64,
+            Metric::TransitiveCallers => metrics.transitive_callers as f64,
+            Metric::TransitiveCallees => metrics.transitive_callees as f64,
+            Metric::Betweenness => metrics.betweenness,
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Config {
+    project_path: PathBuf,
+    project_url: Url,
+    lang_server_exe: String,
+    test_re: Regex,
+    options: CallGraphOptions,
+    dispatch: bool,
+    overrides: bool,
+    use_cache: bool,
+    entries: Vec<EntryPattern>,
+    detect_entries: bool,
+    max_depth: bool,
+    layers: Option<Layers>,
+    chokepoints: Option<usize>,
+    metrics: bool,
+    dead_code_options: DeadCodeOptions,
+    aggregate: Option<Granularity>,
+    break_cycles: bool,
+    mixed_abstraction: Option<usize>,
+    write_baseline: Option<PathBuf>,
+    baseline: Option<Baseline>,
+}
+
+/// Results of analyzing a project
+struct Analysis {
+    arena: SymbolArena,
+    /// All calls (including dispatch / override calls) that weren't filtered
+    calls: Vec<(SymbolId, SymbolId)>,
+    /// Graph of `calls`, built once and shared by all analyses
+    graph: CallGraph<SymbolId>,
+    dispatch_calls: Vec<(SymbolId, SymbolId)>,
+    override_calls: Vec<(SymbolId, SymbolId)>,
+    recursive_clusters: Vec<RecursiveCluster<SymbolId>>,
+    depths: Depths<SymbolId>,
+    /// Longest path to each function, if requested
+    max_depths: Option<MaxDepths<SymbolId>>,
+    /// Declared and detected entry points, empty when using inferred roots
+    entries: Vec<(SymbolId, EntryKind)>,
+    /// Functions not reachable from the entry points, by file
+    unreachable: BTreeMap<String, Vec<SymbolId>>,
+    layer_violations: Vec<LayerViolation>,
+    /// Number of functions dominated by each function, if requested
+    dominated_counts: Option<Vec<(SymbolId, usize)>>,
+    /// Functions that dominate many others, sorted by the number of functions they dominate
+    chokepoints: Vec<(SymbolId, usize)>,
+    metrics: Option<Vec<FunctionMetrics<SymbolId>>>,
+    /// Calls to remove to make the call graph acyclic, with their call sites
+    cycle_breaks: Vec<(FeedbackArc<SymbolId>, Vec<Location>)>,
+    mixed_abstractions: Vec<MixedAbstraction<SymbolId>>,
+    /// Functions with inconsistent depths, without the suppressed ones
+    problems: HashSet<SymbolId>,
+    /// Functions with inconsistent depths and a comment allowing them
+    suppressed: Vec<SymbolId>,
+    /// Functions with a comment allowing inconsistent depths, but consistent depths
+    unused_suppressions: Vec<SymbolId>,
+}
+
 impl Args {
-    fn unpack() -> (Url, String, Regex, LevelFilter) {
+    fn unpack() -> (Option<SubCommand>, Config, LevelFilter) {
         let args = Args::parse();
 
         let project_path = args
@@ -35,9 +295,18 @@ impl Args {
             .expect("given <project_path> couldn't be canonicalized");
 
         let project_url =
-            Url::from_file_path(project_path).expect("failed to convert project path to URL");
+            Url::from_file_path(&project_path).expect("failed to convert project path to URL");
 
-        let lang_server_exe = args.lang_server_exe;
+        let lang_server_exe = match (&args.command, args.lang_server_exe) {
+            (_, Some(lang_server_exe)) => lang_server_exe,
+            (Some(SubCommand::ClearCache), None) => String::new(),
+            (_, None) => Args::command()
+                .error(
+                    ErrorKind::MissingRequiredArgument,
+                    "the following required arguments were not provided:\n  --lang-server-exe <LANG_SERVER_EXE>",
+                )
+                .exit(),
+        };
 
         let test_re = if let Some(test_str) = args.ignore_re {
             Regex::new(&test_str).unwrap_or_else(|_| panic!("invalid regex: '{}'", test_str))
@@ -52,13 +321,66 @@ impl Args {
             _ => LevelFilter::Trace,
         };
 
-        (project_url, lang_server_exe, test_re, verbose)
+        let symbol_kinds = args.symbol_kinds.unwrap_or_else(|| {
+            Language::from_lang_server_exe(&lang_server_exe).callable_symbol_kinds()
+        });
+
+        let options = CallGraphOptions {
+            symbol_kinds,
+            include_external: args.include_external,
+        };
+
+        let layers = args
+            .layers
+            .map(|path| Layers::load(&path).expect("failed to load layers config"));
+
+        let baseline = args
+            .baseline
+            .map(|path| Baseline::load(&path).expect("failed to load baseline"));
+
+        let dead_code_options = DeadCodeOptions {
+            exclude_trait_impls: args
+                .unreachable_exclude
+                .contains(&UnreachableExclusion::TraitImpls),
+            exclude_exported: args
+                .unreachable_exclude
+                .contains(&UnreachableExclusion::Exported),
+        };
+
+        let config = Config {
+            project_path,
+            project_url,
+            lang_server_exe,
+            test_re,
+            options,
+            dispatch: args.dispatch,
+            overrides: args.overrides,
+            use_cache: args.cache,
+            entries: args.entries,
+            detect_entries: args.detect_entries,
+            max_depth: args.max_depth,
+            layers,
+            chokepoints: args.chokepoints,
+            metrics: args.metrics,
+            dead_code_options,
+            aggregate: args.aggregate,
+            break_cycles: args.break_cycles,
+            mixed_abstraction: args.mixed_abstraction,
+            write_baseline: args.write_baseline,
+            baseline,
+        };
+
+        (args.command, config, verbose)
     }
 }
 
+fn parse_symbol_kind(kind: &str) -> Result<SymbolKind, String> {
+    SymbolKind::try_from(kind).map_err(|_| format!("unknown symbol kind: '{}'", kind))
+}
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
-    let (project_url, lang_server_exe, test_re, log_level) = Args::unpack();
+    let (command, config, log_level) = Args::unpack();
 
     simple_logger::SimpleLogger::new()
         .with_level(log_level)
@@ -66,30 +388,674 @@ async fn main() {
         .init()
         .unwrap();
 
-    let server = run_cmd(&lang_server_exe).await;
+    match command {
+        None => {
+            if !analyze(&config).await {
+                std::process::exit(1);
+            }
+        }
+        Some(SubCommand::Watch { interval_ms }) => {
+            watch(&config, Duration::from_millis(interval_ms)).await
+        }
+        Some(SubCommand::Diff { base, head }) => diff_revisions(&config, &base, &head).await,
+        Some(SubCommand::Rank { by, top }) => rank(&config, by, top).await,
+        Some(SubCommand::Path { from, to, all }) => {
+            path(&config, &from, &to, all.unwrap_or(1)).await
+        }
+        Some(SubCommand::Callers { function, depth }) => {
+            call_trees(&config, &function, depth, true).await
+        }
+        Some(SubCommand::Callees { function, depth }) => {
+            call_trees(&config, &function, depth, false).await
+        }
+        Some(SubCommand::Explain { function }) => explain(&config, &function).await,
+        Some(SubCommand::Dsm {
+            granularity,
+            format,
+        }) => dsm(&config, granularity, format).await,
+        Some(SubCommand::ClearCache) => {
+            AnalysisCache::clear(&config.project_path).expect("failed to clear cache")
+        }
+    }
+}
+
+/// Returns false if the run should fail, see [`Report::failed`]
+async fn analyze(config: &Config) -> bool {
+    let mut client = start_client(config).await;
+
+    let workspace_files =
+        code_depth::get_workspace_files(&mut client, &config.project_url, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+    let mut cache = load_cache(config);
+    let report = build_report(&mut client, &workspace_files, &mut cache, config).await;
+
+    println!("{}", serde_json::to_string_pretty(&report.json).unwrap());
+
+    !report.failed
+}
+
+async fn watch(config: &Config, interval: Duration) {
+    let mut client = start_client(config).await;
+
+    let mut workspace_files =
+        code_depth::get_workspace_files(&mut client, &config.project_url, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+    // the cache is kept between reports, so only the changed files are re-queried
+    let mut cache = load_cache(config);
+    let report = build_report(&mut client, &workspace_files, &mut cache, config).await;
+
+    // print each report on a single line, so the output can be streamed
+    println!("{}", report.json);
+
+    let mut watcher = FileWatcher::new(&config.project_path, &workspace_files);
+    let mut document_versions = HashMap::new();
+
+    loop {
+        tokio::time::sleep(interval).await;
+
+        let changes = watcher.poll();
+        if changes.is_empty() {
+            continue;
+        }
+
+        info!("detected {} changed files", changes.len());
+
+        for change in changes {
+            match change {
+                FileChange::Changed(file) => {
+                    let Some(text) = file
+                        .to_file_path()
+                        .ok()
+                        .and_then(|path| std::fs::read_to_string(path).ok())
+                    else {
+                        continue;
+                    };
+
+                    // the first change of each file opens it, later changes update it
+                    match document_versions.get_mut(&file) {
+                        Some(version) => {
+                            *version += 1;
+                            client.did_change(file.clone(), *version, text).await;
+                        }
+                        None => {
+                            let language_id = watch::get_language_id(&file);
+                            client.did_open(file.clone(), &language_id, 0, text).await;
+                            document_versions.insert(file.clone(), 0);
+                        }
+                    }
+
+                    client.did_save(file.clone()).await;
+                    workspace_files.insert(file);
+                }
+                FileChange::Removed(file) => {
+                    if document_versions.remove(&file).is_some() {
+                        client.did_close(file.clone()).await;
+                    }
+
+                    workspace_files.remove(&file);
+                }
+            }
+        }
+
+        let report = build_report(&mut client, &workspace_files, &mut cache, config).await;
+
+        println!("{}", report.json);
+    }
+}
+
+async fn start_client(config: &Config) -> LspClient {
+    let server = run_cmd(&config.lang_server_exe).await;
     let mut client = LspClient::stdio_client(server);
 
-    let response = code_depth::init(&mut client, project_url.clone()).await;
+    let response = code_depth::init(&mut client, config.project_url.clone()).await;
 
     response.expect("failed to init lang server");
 
+    client
+}
+
+fn load_cache(config: &Config) -> AnalysisCache {
+    if config.use_cache {
+        AnalysisCache::load(&config.project_path)
+    } else {
+        AnalysisCache::default()
+    }
+}
+
+async fn rank(config: &Config, by: Metric, top: usize) {
+    let analysis = analyze_once(config).await;
+    let names = analysis.arena.names(&config.project_url);
+
+    let mut metrics = code_depth::get_function_metrics(&analysis.graph);
+    metrics.sort_by(|a, b| by.get(b).total_cmp(&by.get(a)));
+
+    let ranking = metrics
+        .iter()
+        .take(top)
+        .map(|m| {
+            let mut entry = json!(m);
+            entry["function"] = json!(names.get(m.item));
+
+            entry
+        })
+        .collect::<Vec<_>>();
+
+    println!("{}", serde_json::to_string_pretty(&ranking).unwrap());
+}
+
+async fn path(config: &Config, from: &EntryPattern, to: &EntryPattern, max_paths: usize) {
+    let analysis = analyze_once(config).await;
+    let names = analysis.arena.names(&config.project_url);
+
+    let sources = entry::find_entries(
+        &analysis.arena,
+        &config.project_url,
+        std::slice::from_ref(from),
+    );
+    let targets = entry::find_entries(
+        &analysis.arena,
+        &config.project_url,
+        std::slice::from_ref(to),
+    );
+
+    // patterns may match more than one function, so check every pair
+    let mut paths = vec![];
+    for &source in &sources {
+        for &target in &targets {
+            paths.extend(code_depth::get_call_paths(
+                &analysis.graph,
+                source,
+                target,
+                max_paths,
+            ));
+        }
+    }
+
+    paths.sort_by_key(|path| path.len());
+    paths.truncate(max_paths);
+
+    let paths = paths
+        .iter()
+        .map(|path| path.iter().map(|&hop| names.get(hop)).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+
+    println!("{}", serde_json::to_string_pretty(&paths).unwrap());
+}
+
+async fn call_trees(config: &Config, function: &EntryPattern, max_depth: usize, callers: bool) {
+    let analysis = analyze_once(config).await;
+    let names = analysis.arena.names(&config.project_url);
+
+    let trees = entry::find_entries(
+        &analysis.arena,
+        &config.project_url,
+        std::slice::from_ref(function),
+    )
+    .into_iter()
+    .map(|item| {
+        let tree = if callers {
+            code_depth::get_callers_tree(&analysis.graph, item, max_depth)
+        } else {
+            code_depth::get_callees_tree(&analysis.graph, item, max_depth)
+        };
+
+        build_call_tree_json(&tree, &names)
+    })
+    .collect::<Vec<_>>();
+
+    println!("{}", serde_json::to_string_pretty(&trees).unwrap());
+}
+
+fn build_call_tree_json(tree: &CallTree<SymbolId>, names: &SymbolNames) -> Value {
+    json!({
+        "function": names.get(tree.item),
+        "children": tree
+            .children
+            .iter()
+            .map(|child| build_call_tree_json(child, names))
+            .collect::<Vec<_>>(),
+    })
+}
+
+async fn explain(config: &Config, function: &EntryPattern) {
+    let analysis = analyze_once(config).await;
+    let names = analysis.arena.names(&config.project_url);
+
+    let items = entry::find_entries(
+        &analysis.arena,
+        &config.project_url,
+        std::slice::from_ref(function),
+    );
+
+    let short_path = |path: &CallPath<SymbolId>| CallPath {
+        depth: path.depth,
+        path: path
+            .path
+            .iter()
+            .map(|&hop| names.get(hop).to_string())
+            .collect(),
+    };
+
+    for item in items {
+        let name = names.get(item).to_string();
+
+        let paths = analysis
+            .depths
+            .iter()
+            .find(|(other, _)| *other == item)
+            .map(|(_, paths)| paths.iter().map(short_path).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        // with --max-depth, the longest path can be the one reaching the item at another depth
+        let longest = analysis
+            .max_depths
+            .iter()
+            .flatten()
+            .find(|(other, _)| *other == item)
+            .map(|(_, path)| short_path(path));
+
+        match explain::explain(&name, &paths, longest.as_ref()) {
+            Some(explanation) => println!("{}", explain::render(&explanation)),
+            None => match paths.first() {
+                Some(path) => println!("{} is always reached at depth {}\n", name, path.depth),
+                None => println!("{} isn't reached from any root\n", name),
+            },
+        }
+    }
+}
+
+async fn dsm(config: &Config, granularity: Granularity, format: DsmFormat) {
+    let analysis = analyze_once(config).await;
+
+    let group_calls = aggregate::group_calls(
+        &analysis.arena,
+        &config.project_url,
+        &analysis.calls,
+        granularity,
+    );
+
+    let dsm = Dsm::new(&group_calls);
+
+    match format {
+        DsmFormat::Csv => print!("{}", dsm.to_csv()),
+        DsmFormat::Html => print!("{}", dsm.to_html()),
+    }
+}
+
+/// Analyze the project a single time, for commands that query the call graph
+async fn analyze_once(config: &Config) -> Analysis {
+    let mut client = start_client(config).await;
+
+    let workspace_files =
+        code_depth::get_workspace_files(&mut client, &config.project_url, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+    let mut cache = load_cache(config);
+
+    analyze_project(&mut client, &workspace_files, &mut cache, config).await
+}
+
+async fn diff_revisions(config: &Config, base: &str, head: &str) {
+    let base_snapshot = get_revision_snapshot(config, base).await;
+    let head_snapshot = get_revision_snapshot(config, head).await;
+
+    let snapshot_diff = diff_snapshots(&base_snapshot, &head_snapshot);
+
+    println!("{}", serde_json::to_string_pretty(&snapshot_diff).unwrap());
+}
+
+async fn get_revision_snapshot(config: &Config, rev: &str) -> Snapshot {
+    let worktree = Worktree::add(&config.project_path, rev)
+        .unwrap_or_else(|e| panic!("failed to check out '{}': {}", rev, e));
+
+    let project_path = worktree
+        .translate(&config.project_path)
+        .expect("project path isn't inside the git repository");
+
+    let project_url =
+        Url::from_file_path(&project_path).expect("failed to convert project path to URL");
+
+    // item names are relative to the project root, so they match between revisions
+    let config = Config {
+        project_path,
+        project_url: project_url.clone(),
+        use_cache: false,
+        ..config.clone()
+    };
+
+    let mut client = start_client(&config).await;
+
     let workspace_files =
         code_depth::get_workspace_files(&mut client, &project_url, Duration::from_secs(5))
             .await
             .unwrap();
 
-    let calls = code_depth::get_function_calls(&mut client, &workspace_files, &project_url)
-        .await
-        .unwrap();
+    let analysis = analyze_project(
+        &mut client,
+        &workspace_files,
+        &mut AnalysisCache::default(),
+        &config,
+    )
+    .await;
+
+    let names = analysis.arena.names(&project_url);
+
+    let problems = analysis
+        .problems
+        .iter()
+        .map(|&item| names.get(item).to_string())
+        .collect();
+
+    let calls = analysis
+        .calls
+        .iter()
+        .map(|&(s, t)| (names.get(s).to_string(), names.get(t).to_string()))
+        .collect();
+
+    Snapshot {
+        depths: code_depth::build_short_fn_depths(&analysis.arena, &project_url, &analysis.depths),
+        problems,
+        calls,
+    }
+}
+
+/// Results of a single analysis
+struct Report {
+    json: Value,
+    /// Whether the run should fail: layer violations, or problems that are new or got worse
+    /// compared to the baseline
+    failed: bool,
+}
+
+async fn build_report(
+    client: &mut LspClient,
+    workspace_files: &HashSet<Url>,
+    cache: &mut AnalysisCache,
+    config: &Config,
+) -> Report {
+    let analysis = analyze_project(client, workspace_files, cache, config).await;
+
+    let mut results_json = match config.aggregate {
+        Some(granularity) => {
+            build_aggregated_results_json(&analysis, &config.project_url, granularity)
+        }
+        None => build_results_json(&analysis, &config.project_url),
+    };
+
+    // layer violations, suppressions and the baseline are about functions, so they're checked
+    // whatever the granularity of the report
+    add_function_checks_json(&mut results_json, &analysis, &config.project_url);
+
+    let mut baseline_regressed = false;
+    if config.write_baseline.is_some() || config.baseline.is_some() {
+        let current = Baseline::new(
+            &analysis.arena,
+            &config.project_url,
+            &analysis.depths,
+            analysis.max_depths.as_ref(),
+            &analysis.problems,
+        );
+
+        if let Some(path) = &config.write_baseline {
+            current.save(path).expect("failed to write baseline");
+        }
+
+        if let Some(baseline) = &config.baseline {
+            let comparison = baseline.compare(&current);
+
+            baseline_regressed = comparison.has_regressions();
+            results_json["baseline"] = json!(comparison);
+        }
+    }
+
+    for &unused in &analysis.unused_suppressions {
+        warn!(
+            "unused suppression on {}",
+            code_depth::build_call_hierarchy_item_name(
+                analysis.arena.item(unused),
+                &config.project_url
+            )
+        );
+    }
+
+    Report {
+        failed: !analysis.layer_violations.is_empty() || baseline_regressed,
+        json: results_json,
+    }
+}
+
+async fn analyze_project(
+    client: &mut LspClient,
+    workspace_files: &HashSet<Url>,
+    cache: &mut AnalysisCache,
+    config: &Config,
+) -> Analysis {
+    let project_url = &config.project_url;
+
+    let mut calls = code_depth::get_function_calls_cached(
+        client,
+        workspace_files,
+        project_url,
+        &config.options,
+        cache,
+    )
+    .await
+    .unwrap();
+
+    if config.use_cache {
+        cache
+            .save(&config.project_path)
+            .expect("failed to save cache");
+    }
+
+    let mut dispatch_calls = vec![];
+    if config.dispatch {
+        dispatch_calls =
+            code_depth::get_dispatch_calls(client, cache, project_url, &config.options).await;
+
+        calls.extend(dispatch_calls.iter().cloned());
+    }
+
+    let mut override_calls = vec![];
+    if config.overrides {
+        override_calls =
+            code_depth::get_override_calls(client, cache, project_url, &config.options).await;
+
+        calls.extend(override_calls.iter().cloned());
+    }
 
-    let non_test_calls = filter_calls(calls, &test_re, |call: &CallHierarchyItem| {
-        code_depth::build_call_hierarchy_item_name(call, &project_url)
+    let item_to_str =
+        |call: &CallHierarchyItem| code_depth::build_call_hierarchy_item_name(call, project_url);
+
+    let non_test_calls = filter_calls(calls, &config.test_re, item_to_str);
+    let dispatch_calls = filter_calls(dispatch_calls, &config.test_re, item_to_str);
+    let override_calls = filter_calls(override_calls, &config.test_re, item_to_str);
+
+    let mut arena = SymbolArena::default();
+    let calls = arena.intern_calls(non_test_calls);
+    let dispatch_calls = arena.intern_calls(dispatch_calls);
+    let override_calls = arena.intern_calls(override_calls);
+
+    // functions without any calls only matter for entry points and dead code
+    for definition in code_depth::get_definition_items(cache) {
+        if !config.test_re.is_match(&item_to_str(&definition)) {
+            arena.intern(definition);
+        }
+    }
+
+    let graph = CallGraph::new(&calls);
+
+    let language = Language::from_lang_server_exe(&config.lang_server_exe);
+
+    let mut entries = entry::find_entries(&arena, project_url, &config.entries)
+        .into_iter()
+        .map(|id| (id, EntryKind::Declared))
+        .collect::<Vec<_>>();
+
+    if config.detect_entries {
+        for (id, kind) in entry::detect_entries(&arena, language) {
+            if !entries.iter().any(|&(entry, _)| entry == id) {
+                entries.push((id, kind));
+            }
+        }
+    }
+
+    let use_entries = !config.entries.is_empty() || config.detect_entries;
+    let entry_ids = entries.iter().map(|&(id, _)| id).collect::<Vec<_>>();
+
+    let (depths, unreachable) = if !use_entries {
+        let depths = code_depth::get_function_depths(&graph);
+
+        // roots are only functions that are never called, so dead code is told apart from them
+        // with the detected entry points, if there are any for the language
+        let detected = entry::detect_entries(&arena, language)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        let unreachable = if detected.is_empty() {
+            BTreeMap::new()
+        } else {
+            find_dead_code(&arena, &graph, config, language, &detected)
+        };
+
+        (depths, unreachable)
+    } else {
+        info!("found {} entries", entries.len());
+
+        let depths = code_depth::get_function_depths_from_entries(&graph, &entry_ids);
+        let unreachable = find_dead_code(&arena, &graph, config, language, &entry_ids);
+
+        (depths, unreachable)
+    };
+
+    let max_depths = config.max_depth.then(|| {
+        code_depth::get_max_function_depths(&graph, use_entries.then_some(&entry_ids[..]))
     });
 
-    let depths = code_depth::get_function_depths(non_test_calls);
-    let results_json = build_results_json(&depths, &project_url);
+    let mut chokepoints = vec![];
+    let dominated_counts = config.chokepoints.map(|min_dominated| {
+        let dominated_counts = code_depth::get_dominated_function_counts(
+            &graph,
+            use_entries.then_some(&entry_ids[..]),
+        );
+
+        // a root's path to itself has a single hop
+        let roots = depths
+            .iter()
+            .filter(|(_, paths)| paths.iter().any(|path| path.path.len() == 1))
+            .map(|&(id, _)| id)
+            .collect::<HashSet<_>>();
+
+        chokepoints = dominated_counts
+            .iter()
+            .filter(|(id, count)| *count >= min_dominated && !roots.contains(id))
+            .cloned()
+            .collect();
 
-    println!("{}", serde_json::to_string_pretty(&results_json).unwrap());
+        chokepoints.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+
+        dominated_counts
+    });
+
+    let metrics = config
+        .metrics
+        .then(|| code_depth::get_function_metrics(&graph));
+
+    let recursive_clusters = code_depth::get_recursive_clusters(&graph);
+
+    let mixed_abstractions = config
+        .mixed_abstraction
+        .map(|min_spread| abstraction::find_mixed_abstractions(&graph, min_spread))
+        .unwrap_or_default();
+
+    // suppressions hiding a problem are used, the others are reported
+    let mut problems = code_depth::find_items_with_different_depths(&depths, max_depths.as_ref());
+    let (suppressed, unused_suppressions) =
+        suppression::find_suppressions(&arena, project_url, suppression::INCONSISTENT_DEPTH)
+            .into_iter()
+            .partition(|id| problems.remove(id));
+
+    let mut cycle_breaks = vec![];
+    if config.break_cycles {
+        for arc in code_depth::get_cycle_breaks(&graph) {
+            let call_sites =
+                code_depth::get_call_sites(client, arena.item(arc.caller), arena.item(arc.callee))
+                    .await;
+
+            cycle_breaks.push((arc, call_sites));
+        }
+    }
+
+    // dispatch / override calls have no call site, so only direct calls are checked
+    let mut layer_violations = vec![];
+    if let Some(layers) = &config.layers {
+        let direct_calls = calls
+            .iter()
+            .filter(|call| !dispatch_calls.contains(call) && !override_calls.contains(call))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        layer_violations = layers.find_violations(&arena, project_url, &direct_calls);
+
+        for violation in layer_violations.iter_mut() {
+            violation.call_sites = code_depth::get_call_sites(
+                client,
+                arena.item(violation.caller),
+                arena.item(violation.callee),
+            )
+            .await;
+        }
+    }
+
+    Analysis {
+        arena,
+        calls,
+        graph,
+        dispatch_calls,
+        override_calls,
+        recursive_clusters,
+        depths,
+        max_depths,
+        entries,
+        unreachable,
+        layer_violations,
+        dominated_counts,
+        chokepoints,
+        metrics,
+        cycle_breaks,
+        mixed_abstractions,
+        problems,
+        suppressed,
+        unused_suppressions,
+    }
+}
+
+/// Project functions that aren't reached from any of the entries, see
+/// [`dead_code::find_unreachable`]
+fn find_dead_code(
+    arena: &SymbolArena,
+    graph: &CallGraph<SymbolId>,
+    config: &Config,
+    language: Language,
+    entries: &[SymbolId],
+) -> BTreeMap<String, Vec<SymbolId>> {
+    // entries without any calls aren't in the graph, but they're still reached
+    let mut reached = code_depth::get_reachable_functions(graph, entries);
+    reached.extend(entries.iter().copied());
+
+    dead_code::find_unreachable(
+        arena,
+        &config.project_url,
+        &reached,
+        language,
+        config.dead_code_options,
+    )
 }
 
 async fn run_cmd(cmd: &str) -> Child {
@@ -111,20 +1077,21 @@ async fn run_cmd(cmd: &str) -> Child {
         .unwrap_or_else(|_| panic!("failed to run: '{}'", cmd))
 }
 
-fn build_results_json(depths: &Depths<CallHierarchyItem>, project_url: &Url) -> Value {
+fn build_results_json(analysis: &Analysis, project_url: &Url) -> Value {
+    let depths = &analysis.depths;
+    let names = analysis.arena.names(project_url);
     let mut results_json = json!({});
 
     results_json["ok"] = json!({});
     results_json["problems"] = json!({});
 
-    // find all items with different depths
-    let problem_items =
-        code_depth::find_items_with_different_depths::<_, HashableCallHierarchyItem>(depths)
-            .iter()
-            .map(|item| code_depth::build_call_hierarchy_item_name(&item.0, project_url))
-            .collect::<HashSet<_>>();
+    let problem_items = analysis
+        .problems
+        .iter()
+        .map(|&item| names.get(item).to_string())
+        .collect::<HashSet<_>>();
 
-    code_depth::build_short_fn_depths(project_url, depths)
+    code_depth::build_short_fn_depths(&analysis.arena, project_url, depths)
         .iter()
         .for_each(|(item_name, item_depths_from_roots)| {
             let item_depths_from_roots = serde_json::to_value(item_depths_from_roots).unwrap();
@@ -136,6 +1103,212 @@ fn build_results_json(depths: &Depths<CallHierarchyItem>, project_url: &Url) ->
             }
         });
 
+    // mark dispatch / override calls, so they can be told apart from direct calls in the paths
+    let call_names = |calls: &Vec<(SymbolId, SymbolId)>| -> Value {
+        calls
+            .iter()
+            .map(|&(s, t)| json!([names.get(s), names.get(t)]))
+            .collect()
+    };
+
+    if !analysis.dispatch_calls.is_empty() {
+        results_json["dispatch"] = call_names(&analysis.dispatch_calls);
+    }
+
+    if !analysis.override_calls.is_empty() {
+        results_json["override"] = call_names(&analysis.override_calls);
+    }
+
+    if let Some(max_depths) = &analysis.max_depths {
+        results_json["max_depths"] = max_depths
+            .iter()
+            .map(|(item, path)| {
+                let hops = path
+                    .path
+                    .iter()
+                    .map(|&hop| names.get(hop))
+                    .collect::<Vec<_>>();
+
+                (
+                    names.get(*item).to_string(),
+                    json!({ "depth": path.depth, "path": hops }),
+                )
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+
+    if let Some(dominated_counts) = &analysis.dominated_counts {
+        results_json["dominated"] = dominated_counts
+            .iter()
+            .filter(|&&(_, count)| count > 0)
+            .map(|&(id, count)| (names.get(id).to_string(), json!(count)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+
+        results_json["chokepoints"] = analysis
+            .chokepoints
+            .iter()
+            .map(|&(id, count)| json!([names.get(id), count]))
+            .collect();
+    }
+
+    if let Some(metrics) = &analysis.metrics {
+        results_json["metrics"] = metrics
+            .iter()
+            .map(|m| (names.get(m.item).to_string(), json!(m)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+
+    // tag each entry point with the reason it is one
+    if !analysis.entries.is_empty() {
+        results_json["entries"] = analysis
+            .entries
+            .iter()
+            .map(|&(id, kind)| (names.get(id).to_string(), json!(kind)))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+
+    if !analysis.unreachable.is_empty() {
+        results_json["unreachable"] = analysis
+            .unreachable
+            .iter()
+            .map(|(file, items)| {
+                let items = items
+                    .iter()
+                    .map(|&item| names.get(item))
+                    .collect::<Vec<_>>();
+
+                (file.clone(), json!(items))
+            })
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+
+    if !analysis.mixed_abstractions.is_empty() {
+        results_json["mixed_abstraction"] = analysis
+            .mixed_abstractions
+            .iter()
+            .map(|offender| {
+                json!({
+                    "function": names.get(offender.function),
+                    "spread": offender.spread,
+                    "highest": [names.get(offender.highest.0), offender.highest.1],
+                    "lowest": [names.get(offender.lowest.0), offender.lowest.1],
+                })
+            })
+            .collect();
+    }
+
+    if !analysis.cycle_breaks.is_empty() {
+        results_json["cycle_breaks"] = analysis
+            .cycle_breaks
+            .iter()
+            .map(|(arc, call_sites)| {
+                json!({
+                    "caller": names.get(arc.caller),
+                    "callee": names.get(arc.callee),
+                    "cycles": arc.cycles,
+                    "cycles_truncated": arc.truncated,
+                    "call_sites": build_call_sites_json(call_sites, project_url),
+                })
+            })
+            .collect();
+    }
+
+    if !analysis.recursive_clusters.is_empty() {
+        results_json["recursion"] = analysis
+            .recursive_clusters
+            .iter()
+            .map(|cluster| {
+                json!({
+                    "members": cluster.members.iter().map(|&m| names.get(m)).collect::<Vec<_>>(),
+                    "entries": call_names(&cluster.entries),
+                })
+            })
+            .collect();
+    }
+
+    results_json
+}
+
+/// Format call sites as "path:line:column", like compiler diagnostics
+fn build_call_sites_json(call_sites: &[Location], project_url: &Url) -> Value {
+    call_sites
+        .iter()
+        .map(|site| {
+            json!(format!(
+                "{}:{}:{}",
+                site.uri.as_str().trim_start_matches(project_url.as_str()),
+                site.range.start.line + 1,
+                site.range.start.character + 1
+            ))
+        })
+        .collect()
+}
+
+/// Add the sections checked on functions: used and unused suppressions, and layer violations
+fn add_function_checks_json(results_json: &mut Value, analysis: &Analysis, project_url: &Url) {
+    let names = analysis.arena.names(project_url);
+
+    let item_names =
+        |items: &[SymbolId]| -> Value { items.iter().map(|&id| names.get(id)).collect() };
+
+    if !analysis.suppressed.is_empty() {
+        results_json["suppressed"] = item_names(&analysis.suppressed);
+    }
+
+    if !analysis.unused_suppressions.is_empty() {
+        results_json["unused_suppressions"] = item_names(&analysis.unused_suppressions);
+    }
+
+    if !analysis.layer_violations.is_empty() {
+        results_json["layer_violations"] = analysis
+            .layer_violations
+            .iter()
+            .map(|violation| {
+                let call_sites = build_call_sites_json(&violation.call_sites, project_url);
+
+                json!({
+                    "kind": violation.kind,
+                    "caller": names.get(violation.caller),
+                    "callee": names.get(violation.callee),
+                    "caller_layer": violation.caller_layer,
+                    "callee_layer": violation.callee_layer,
+                    "call_sites": call_sites,
+                })
+            })
+            .collect();
+    }
+}
+
+fn build_aggregated_results_json(
+    analysis: &Analysis,
+    project_url: &Url,
+    granularity: Granularity,
+) -> Value {
+    let calls =
+        aggregate::aggregate_calls(&analysis.arena, project_url, &analysis.calls, granularity);
+    let depths = code_depth::get_aggregated_depths(&calls);
+
+    let problem_groups = code_depth::find_items_with_different_depths::<_, String>(&depths, None);
+
+    let mut results_json = json!({});
+    results_json["ok"] = json!({});
+    results_json["problems"] = json!({});
+
+    for (group, group_depths_from_roots) in depths {
+        let section = if problem_groups.contains(&group) {
+            "problems"
+        } else {
+            "ok"
+        };
+
+        results_json[section][group] = json!(group_depths_from_roots);
+    }
+
     results_json
 }
 
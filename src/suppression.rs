@@ -0,0 +1,112 @@
+use std::{collections::HashMap, fs};
+
+use lsp_types::{CallHierarchyItem, Url};
+
+use crate::{
+    entry::get_definition_head,
+    symbol_arena::{SymbolArena, SymbolId},
+};
+
+/// Rule of `find_items_with_different_depths`
+pub const INCONSISTENT_DEPTH: &str = "inconsistent-depth";
+
+const MARKER: &str = "code-depth: allow(";
+
+/// Find project items suppressing `rule`, with a `// code-depth: allow(<rule>)` comment
+/// on the line of their name or in the comments / attributes right above them
+pub fn find_suppressions(arena: &SymbolArena, root: &Url, rule: &str) -> Vec<SymbolId> {
+    let mut sources: HashMap<Url, Option<String>> = HashMap::new();
+
+    arena
+        .ids()
+        .filter(|&id| {
+            let item = arena.item(id);
+            if !item.uri.as_str().starts_with(root.as_str()) {
+                return false;
+            }
+
+            let source = sources.entry(item.uri.clone()).or_insert_with(|| {
+                let path = item.uri.to_file_path().ok()?;
+
+                fs::read_to_string(path).ok()
+            });
+
+            source
+                .as_deref()
+                .is_some_and(|source| is_suppressed(&get_suppression_text(source, item), rule))
+        })
+        .collect()
+}
+
+fn get_suppression_text(source: &str, item: &CallHierarchyItem) -> String {
+    let name_line = source
+        .lines()
+        .nth(item.selection_range.start.line as usize)
+        .unwrap_or_default();
+
+    get_definition_head(source, item) + name_line
+}
+
+/// Whether `text` has a marker allowing `rule`, e.g. "code-depth: allow(rule, other-rule)"
+fn is_suppressed(text: &str, rule: &str) -> bool {
+    text.match_indices(MARKER).any(|(i, _)| {
+        let rules = &text[i + MARKER.len()..];
+
+        rules
+            .split_once(')')
+            .is_some_and(|(rules, _)| rules.split(',').any(|r| r.trim() == rule))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use super::{get_suppression_text, is_suppressed, INCONSISTENT_DEPTH};
+    use crate::test_util::item_at;
+
+    #[test]
+    fn test_is_suppressed() {
+        assert!(is_suppressed(
+            "// code-depth: allow(inconsistent-depth)",
+            INCONSISTENT_DEPTH
+        ));
+        assert!(is_suppressed(
+            "// code-depth: allow(other, inconsistent-depth)",
+            INCONSISTENT_DEPTH
+        ));
+        assert!(!is_suppressed(
+            "// code-depth: allow(other)",
+            INCONSISTENT_DEPTH
+        ));
+        assert!(!is_suppressed(
+            "// allow(inconsistent-depth)",
+            INCONSISTENT_DEPTH
+        ));
+    }
+
+    #[test]
+    fn test_get_suppression_text() {
+        let source = "// code-depth: allow(inconsistent-depth)\n\
+                      fn a() {}\n\
+                      \n\
+                      fn b() {} // code-depth: allow(inconsistent-depth)\n\
+                      \n\
+                      fn c() {}\n";
+
+        for (line, suppressed) in [(1, true), (3, true), (5, false)] {
+            let text = get_suppression_text(
+                source,
+                &item_at(
+                    "f",
+                    SymbolKind::FUNCTION,
+                    "file:///project/src/log.rs",
+                    line,
+                    3,
+                ),
+            );
+
+            assert_eq!(is_suppressed(&text, INCONSISTENT_DEPTH), suppressed);
+        }
+    }
+}
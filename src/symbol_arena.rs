@@ -0,0 +1,105 @@
+use std::collections::HashMap;
+
+use lsp_types::{CallHierarchyItem, Range, Url};
+
+use crate::build_call_hierarchy_item_name;
+
+/// Compact id of an item interned in a [`SymbolArena`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SymbolId(u32);
+
+/// Stores each call hierarchy item once, so the rest of the pipeline can use [`SymbolId`]s
+#[derive(Debug, Default)]
+pub struct SymbolArena {
+    items: Vec<CallHierarchyItem>,
+    ids: HashMap<(Url, [u32; 4]), SymbolId>,
+}
+
+/// Items are identified by their uri and range
+fn get_key(item: &CallHierarchyItem) -> (Url, [u32; 4]) {
+    let Range { start, end } = item.range;
+    (
+        item.uri.clone(),
+        [start.line, start.character, end.line, end.character],
+    )
+}
+
+impl SymbolArena {
+    pub fn intern(&mut self, item: CallHierarchyItem) -> SymbolId {
+        let next_id = SymbolId(self.items.len() as u32);
+        let key = get_key(&item);
+
+        if let Some(&id) = self.ids.get(&key) {
+            return id;
+        }
+
+        self.items.push(item);
+        self.ids.insert(key, next_id);
+
+        next_id
+    }
+
+    pub fn intern_calls(
+        &mut self,
+        calls: Vec<(CallHierarchyItem, CallHierarchyItem)>,
+    ) -> Vec<(SymbolId, SymbolId)> {
+        calls
+            .into_iter()
+            .map(|(s, t)| (self.intern(s), self.intern(t)))
+            .collect()
+    }
+
+    /// Ids of all items, in order of interning
+    pub fn ids(&self) -> impl Iterator<Item = SymbolId> {
+        (0..self.items.len() as u32).map(SymbolId)
+    }
+
+    pub fn item(&self, id: SymbolId) -> &CallHierarchyItem {
+        &self.items[id.0 as usize]
+    }
+
+    /// Names of all items, indexed by id
+    pub fn names(&self, root: &Url) -> SymbolNames {
+        SymbolNames(
+            self.items
+                .iter()
+                .map(|item| build_call_hierarchy_item_name(item, root))
+                .collect(),
+        )
+    }
+}
+
+/// Names of all items in a [`SymbolArena`], resolved once for output
+pub struct SymbolNames(Vec<String>);
+
+impl SymbolNames {
+    pub fn get(&self, id: SymbolId) -> &str {
+        &self.0[id.0 as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::SymbolKind;
+
+    use super::*;
+    use crate::test_util::item_at;
+
+    const LIB: &str = "file:///project/src/lib.rs";
+
+    #[test]
+    fn test_intern() {
+        let mut arena = SymbolArena::default();
+
+        let a = arena.intern(item_at("a", SymbolKind::FUNCTION, LIB, 0, 0));
+        let b = arena.intern(item_at("b", SymbolKind::FUNCTION, LIB, 1, 0));
+
+        assert_ne!(a, b);
+        assert_eq!(
+            arena.intern(item_at("a2", SymbolKind::FUNCTION, LIB, 0, 0)),
+            a
+        );
+        assert_eq!(arena.ids().collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(arena.item(a).name, "a");
+    }
+}
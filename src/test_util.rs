@@ -0,0 +1,34 @@
+//! Builders shared by the unit tests
+
+use lsp_types::{CallHierarchyItem, Position, Range, SymbolKind, Url};
+
+/// Item without a position, for tests that only look at names and files
+pub(crate) fn item(name: &str, kind: SymbolKind, uri: &str) -> CallHierarchyItem {
+    CallHierarchyItem {
+        name: name.to_string(),
+        kind,
+        tags: None,
+        detail: None,
+        uri: Url::parse(uri).unwrap(),
+        range: Range::default(),
+        selection_range: Range::default(),
+        data: None,
+    }
+}
+
+/// Item whose name starts at `line`:`character`, with its range starting at the beginning of that line
+pub(crate) fn item_at(
+    name: &str,
+    kind: SymbolKind,
+    uri: &str,
+    line: u32,
+    character: u32,
+) -> CallHierarchyItem {
+    let position = Position::new(line, character);
+
+    CallHierarchyItem {
+        range: Range::new(Position::new(line, 0), position),
+        selection_range: Range::new(position, position),
+        ..item(name, kind, uri)
+    }
+}
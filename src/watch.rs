@@ -0,0 +1,191 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsString,
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use lsp_types::Url;
+
+/// Directories that never contain project sources
+const IGNORED_DIRS: [&str; 2] = ["target", "node_modules"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileChange {
+    /// File was created or its content was modified
+    Changed(Url),
+    Removed(Url),
+}
+
+/// Polls the project tree for changes to source files
+pub struct FileWatcher {
+    root: PathBuf,
+    extensions: HashSet<OsString>,
+    modified: HashMap<PathBuf, SystemTime>,
+}
+
+impl FileWatcher {
+    /// Watch all files under `root` with the same extensions as the given workspace files
+    pub fn new(root: &Path, workspace_files: &HashSet<Url>) -> Self {
+        let extensions = workspace_files
+            .iter()
+            .filter_map(|file| file.to_file_path().ok())
+            .filter_map(|path| path.extension().map(|e| e.to_os_string()))
+            .collect();
+
+        let mut watcher = Self {
+            root: root.to_path_buf(),
+            extensions,
+            modified: HashMap::new(),
+        };
+
+        watcher.modified = watcher.scan();
+
+        watcher
+    }
+
+    /// Get all changes since the last poll
+    pub fn poll(&mut self) -> Vec<FileChange> {
+        let modified = self.scan();
+
+        let mut changes = vec![];
+        for (path, time) in modified.iter() {
+            if self.modified.get(path) != Some(time) {
+                if let Ok(uri) = Url::from_file_path(path) {
+                    changes.push(FileChange::Changed(uri));
+                }
+            }
+        }
+
+        for path in self.modified.keys() {
+            if !modified.contains_key(path) {
+                if let Ok(uri) = Url::from_file_path(path) {
+                    changes.push(FileChange::Removed(uri));
+                }
+            }
+        }
+
+        self.modified = modified;
+
+        changes
+    }
+
+    fn scan(&self) -> HashMap<PathBuf, SystemTime> {
+        let mut modified = HashMap::new();
+        let mut dirs = vec![self.root.clone()];
+
+        while let Some(dir) = dirs.pop() {
+            let Ok(entries) = fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+
+                if metadata.is_dir() {
+                    let name = entry.file_name();
+                    let name = name.to_string_lossy();
+
+                    // skip hidden dirs (.git, .code_depth, ...) and build outputs
+                    if !name.starts_with('.') && !IGNORED_DIRS.contains(&name.as_ref()) {
+                        dirs.push(path);
+                    }
+                } else if path
+                    .extension()
+                    .is_some_and(|e| self.extensions.contains(e))
+                {
+                    if let Ok(time) = metadata.modified() {
+                        modified.insert(path, time);
+                    }
+                }
+            }
+        }
+
+        modified
+    }
+}
+
+/// Get the lsp language id of a file, based on its extension
+pub fn get_language_id(uri: &Url) -> String {
+    let extension = uri
+        .path()
+        .rsplit_once('.')
+        .map_or("", |(_, extension)| extension);
+
+    match extension {
+        "rs" => "rust",
+        "java" => "java",
+        "kt" => "kotlin",
+        "ts" => "typescript",
+        "tsx" => "typescriptreact",
+        "js" => "javascript",
+        "py" => "python",
+        "go" => "go",
+        other => other,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        fs,
+        time::{Duration, SystemTime},
+    };
+
+    use lsp_types::Url;
+
+    use super::{get_language_id, FileChange, FileWatcher};
+
+    #[test]
+    fn test_poll() {
+        let dir = std::env::temp_dir().join(format!("code_depth_watch_{}", std::process::id()));
+        fs::create_dir_all(dir.join("target")).unwrap();
+        fs::write(dir.join("a.rs"), "fn a() {}").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let a = Url::from_file_path(dir.join("a.rs")).unwrap();
+        let mut watcher = FileWatcher::new(&dir, &HashSet::from([a.clone()]));
+        assert!(watcher.poll().is_empty());
+
+        // only files with the extensions of the workspace files are watched, outside of
+        // build outputs
+        fs::write(dir.join("b.rs"), "fn b() {}").unwrap();
+        fs::write(dir.join("target").join("c.rs"), "fn c() {}").unwrap();
+        fs::write(dir.join("notes.txt"), "changed").unwrap();
+
+        let b = Url::from_file_path(dir.join("b.rs")).unwrap();
+        assert_eq!(watcher.poll(), vec![FileChange::Changed(b)]);
+
+        // some file systems only store modification times in seconds
+        fs::File::options()
+            .write(true)
+            .open(dir.join("a.rs"))
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+        assert_eq!(watcher.poll(), vec![FileChange::Changed(a.clone())]);
+
+        fs::remove_file(dir.join("a.rs")).unwrap();
+        assert_eq!(watcher.poll(), vec![FileChange::Removed(a)]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_get_language_id() {
+        let language_id = |path: &str| {
+            get_language_id(&Url::parse(&format!("file:///project/{}", path)).unwrap())
+        };
+
+        assert_eq!(language_id("src/main.rs"), "rust");
+        assert_eq!(language_id("src/App.java"), "java");
+        assert_eq!(language_id("src/app.tsx"), "typescriptreact");
+        assert_eq!(language_id("src/main.zig"), "zig");
+    }
+}
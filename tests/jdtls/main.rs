@@ -3,7 +3,9 @@ use std::{path::Path, process::Stdio, time::Duration};
 use lsp_types::Url;
 use tokio::process::Command;
 
-use code_depth::{self, lsp::LspClient};
+use code_depth::{
+    self, language::Language, lsp::LspClient, symbol_arena::SymbolArena, CallGraph, CallPath,
+};
 
 const SAMPLE_PROJECT_PATH: &str = "tests/jdtls/sample_java_project";
 
@@ -42,7 +44,12 @@ async fn test_lsp_client() {
             .await
             .expect("get_function_definitions failed");
 
-    let calls = code_depth::get_function_calls(&mut client, &workspace_files, &root)
+    let options = code_depth::CallGraphOptions {
+        symbol_kinds: Language::Java.callable_symbol_kinds(),
+        ..Default::default()
+    };
+
+    let calls = code_depth::get_function_calls(&mut client, &workspace_files, &root, &options)
         .await
         .expect("get_function_calls failed");
 
@@ -80,34 +87,45 @@ async fn test_lsp_client() {
         "didn't find all function calls"
     );
 
-    let depths = code_depth::get_function_depths(calls);
+    let mut arena = SymbolArena::default();
+    let calls = arena.intern_calls(calls);
+
+    let depths = code_depth::get_function_depths(&CallGraph::new(&calls));
 
-    let short_item_depths = code_depth::build_short_fn_depths(&root, &depths);
+    let short_item_depths = code_depth::build_short_fn_depths(&arena, &root, &depths);
 
     assert!(short_item_depths.contains(&(
         "/src/main/java/sample/OtherFile.java:otherFileMethod".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main/java/sample/App.java:main".into(),
             "/src/main/java/sample/App.java:method".into(),
             "/src/main/java/sample/OtherFile.java:otherFileMethod".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main/java/sample/App.java:method".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main/java/sample/App.java:main".into(),
             "/src/main/java/sample/App.java:method".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main/java/sample/App.java:foo".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main/java/sample/App.java:main".into(),
             "/src/main/java/sample/App.java:foo".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main/java/sample/App.java:main".into(),
-        vec![vec!["/src/main/java/sample/App.java:main".into(),],],
+        vec![chain(vec!["/src/main/java/sample/App.java:main".into(),])],
     )));
 }
+
+/// Path without recursive clusters, where each call adds to the depth
+fn chain(path: Vec<String>) -> CallPath<String> {
+    CallPath {
+        depth: path.len() - 1,
+        path,
+    }
+}
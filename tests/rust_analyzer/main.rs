@@ -3,7 +3,7 @@ use std::{path::Path, process::Stdio, time::Duration};
 use lsp_types::Url;
 use tokio::process::Command;
 
-use code_depth::{self, lsp::LspClient};
+use code_depth::{self, lsp::LspClient, symbol_arena::SymbolArena, CallGraph, CallPath};
 
 const SAMPLE_PROJECT_PATH: &str = "tests/rust_analyzer/sample_rust_project";
 
@@ -37,7 +37,9 @@ async fn test_lsp_client() {
         .await
         .expect("get_function_definitions failed");
 
-    let calls = code_depth::get_function_calls(&mut client, &definitions, &root)
+    let options = code_depth::CallGraphOptions::default();
+
+    let calls = code_depth::get_function_calls(&mut client, &definitions, &root, &options)
         .await
         .expect("get_function_calls failed");
 
@@ -76,39 +78,53 @@ async fn test_lsp_client() {
         "didn't find all function calls"
     );
 
-    let depths = code_depth::get_function_depths(calls);
+    let mut arena = SymbolArena::default();
+    let calls = arena.intern_calls(calls);
+
+    let depths = code_depth::get_function_depths(&CallGraph::new(&calls));
 
-    let short_item_depths = code_depth::build_short_fn_depths(&root, &depths);
+    let short_item_depths = code_depth::build_short_fn_depths(&arena, &root, &depths);
 
     assert!(short_item_depths.contains(&(
         "/src/other_file.rs:other_file_method".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main.rs:main".into(),
             "/src/main.rs:impl_method".into(),
             "/src/other_file.rs:other_file_method".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main.rs:in_foo".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main.rs:main".into(),
             "/src/main.rs:foo".into(),
             "/src/main.rs:in_foo".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main.rs:impl_method".into(),
-        vec![vec![
+        vec![chain(vec![
             "/src/main.rs:main".into(),
             "/src/main.rs:impl_method".into(),
-        ],],
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main.rs:foo".into(),
-        vec![vec!["/src/main.rs:main".into(), "/src/main.rs:foo".into(),],],
+        vec![chain(vec![
+            "/src/main.rs:main".into(),
+            "/src/main.rs:foo".into(),
+        ])],
     )));
     assert!(short_item_depths.contains(&(
         "/src/main.rs:main".into(),
-        vec![vec!["/src/main.rs:main".into(),],],
+        vec![chain(vec!["/src/main.rs:main".into(),])],
     )));
 }
+
+/// Path without recursive clusters, where each call adds to the depth
+fn chain(path: Vec<String>) -> CallPath<String> {
+    CallPath {
+        depth: path.len() - 1,
+        path,
+    }
+}